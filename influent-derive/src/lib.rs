@@ -0,0 +1,130 @@
+//! `#[derive(ToMeasurement)]` for turning structs into `influent::measurement::Measurement`.
+//!
+//! The measurement name is the struct name, unless set with `#[influx(measurement = "name")]`.
+//! Fields become measurement fields, unless marked with `#[influx(tag)]`, `#[influx(timestamp)]`
+//! or `#[influx(skip)]`. `Option` fields are omitted when `None`.
+//!
+//! Field values must be convertible with `Value::from(&field)`, tag values must implement
+//! `Display`, and the timestamp must be `i64` nanoseconds.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, LitStr, Type};
+
+#[derive(PartialEq)]
+enum Kind {
+    Field,
+    Tag,
+    Timestamp,
+    Skip
+}
+
+#[proc_macro_derive(ToMeasurement, attributes(influx))]
+pub fn derive_to_measurement(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into()
+    }
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let mut key = input.ident.to_string();
+
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("influx")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("measurement") {
+                key = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else {
+                Err(meta.error("expected `measurement = \"...\"`"))
+            }
+        })?;
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "ToMeasurement requires named fields"))
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "ToMeasurement could only be derived for structs"))
+    };
+
+    let mut statements = vec![];
+    let mut has_timestamp = false;
+
+    for field in fields {
+        let mut kind = Kind::Field;
+
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("influx")) {
+            attr.parse_nested_meta(|meta| {
+                kind = if meta.path.is_ident("tag") {
+                    Kind::Tag
+                } else if meta.path.is_ident("timestamp") {
+                    Kind::Timestamp
+                } else if meta.path.is_ident("skip") {
+                    Kind::Skip
+                } else {
+                    return Err(meta.error("expected `tag`, `timestamp` or `skip`"));
+                };
+                Ok(())
+            })?;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let optional = is_option(&field.ty);
+
+        let value = match kind {
+            Kind::Skip => continue,
+            Kind::Field => quote! { measurement.add_field(#name, ::influent::measurement::Value::from(value)); },
+            Kind::Tag => quote! { measurement.add_tag(#name, value.to_string()); },
+            Kind::Timestamp => {
+                if has_timestamp {
+                    return Err(Error::new_spanned(ident, "only one field could be the timestamp"));
+                }
+                has_timestamp = true;
+                quote! { measurement.set_timestamp(*value); }
+            }
+        };
+
+        statements.push(if optional {
+            quote! {
+                if let Some(ref value) = self.#ident {
+                    #value
+                }
+            }
+        } else {
+            quote! {
+                {
+                    let value = &self.#ident;
+                    #value
+                }
+            }
+        });
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::influent::measurement::ToMeasurement for #ident #ty_generics #where_clause {
+            fn to_measurement(&self) -> ::influent::measurement::Measurement<'_> {
+                let mut measurement = ::influent::measurement::Measurement::new(#key);
+                #(#statements)*
+                measurement
+            }
+        }
+    })
+}
+
+fn is_option(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref path) => path.qself.is_none() && path.path.segments.last().map(|s| s.ident == "Option").unwrap_or(false),
+        _ => false
+    }
+}
@@ -0,0 +1,52 @@
+use influent::measurement::ToMeasurement;
+use influent::serializer::line::LineSerializer;
+use influent::serializer::Serializer;
+
+#[derive(ToMeasurement)]
+#[influx(measurement = "cpu")]
+struct Cpu {
+    #[influx(tag)]
+    host: String,
+    #[influx(tag)]
+    region: Option<&'static str>,
+    usage: f64,
+    cores: i64,
+    model: Option<String>,
+    #[influx(skip)]
+    #[allow(dead_code)]
+    internal: u64,
+    #[influx(timestamp)]
+    time: i64
+}
+
+#[derive(ToMeasurement)]
+struct Memory {
+    free: i64,
+    #[influx(timestamp)]
+    time: Option<i64>
+}
+
+#[test]
+fn test_derive() {
+    let cpu = Cpu {
+        host: "server01".to_string(),
+        region: Some("us-west"),
+        usage: 0.64,
+        cores: 8,
+        model: None,
+        internal: 1,
+        time: 1434055562000000000
+    };
+
+    assert_eq!(
+        "cpu,host=server01,region=us-west cores=8i,usage=0.64 1434055562000000000",
+        LineSerializer::new().serialize(&cpu.to_measurement())
+    );
+}
+
+#[test]
+fn test_derive_default_name() {
+    let memory = Memory { free: 10, time: None };
+
+    assert_eq!("Memory free=10i", LineSerializer::new().serialize(&memory.to_measurement()));
+}
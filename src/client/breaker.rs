@@ -0,0 +1,159 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Circuit breaker, which stops sending requests to InfluxDB after a number of consecutive
+/// failures, so that callers fail fast instead of waiting for every request to time out.
+///
+/// After `threshold` consecutive failures the circuit opens, and requests are rejected with
+/// `ClientError::CircuitOpen` for the `cooldown` period. Then a single probe request is let through:
+/// if it succeeds the circuit closes, otherwise it opens for another `cooldown` period.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<State>
+}
+
+#[derive(Debug)]
+struct State {
+    failures: u32,
+    opened_at: Option<Instant>,
+    probing: bool
+}
+
+impl CircuitBreaker {
+    /// Constructs new closed `CircuitBreaker`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::breaker::CircuitBreaker;
+    /// use std::time::Duration;
+    ///
+    /// let breaker = CircuitBreaker::new(5, Duration::from_secs(30));
+    /// ```
+    pub fn new(threshold: u32, cooldown: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            threshold,
+            cooldown,
+            state: Mutex::new(State {
+                failures: 0,
+                opened_at: None,
+                probing: false
+            })
+        }
+    }
+
+    /// Returns whether the circuit is open, i.e. requests are rejected.
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().opened_at.is_some()
+    }
+
+    /// Checks whether a request could be sent.
+    pub fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        match state.opened_at {
+            None => true,
+            Some(_) if state.probing => false,
+            Some(opened_at) if opened_at.elapsed() >= self.cooldown => {
+                state.probing = true;
+                true
+            },
+            Some(_) => false
+        }
+    }
+
+    /// Records the outcome of a request.
+    pub fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.probing = false;
+
+        if success {
+            state.failures = 0;
+            state.opened_at = None;
+            return;
+        }
+
+        state.failures = state.failures.saturating_add(1);
+
+        if state.opened_at.is_some() || state.failures >= self.threshold {
+            state.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Releases the probe let through by `allow` without recording an outcome, e.g. when the
+    /// request was cancelled, so the next request could probe instead.
+    pub fn release(&self) {
+        self.state.lock().unwrap().probing = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CircuitBreaker;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_open_after_threshold() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record(false);
+        assert!(breaker.allow());
+
+        breaker.record(false);
+        assert!(breaker.is_open());
+        assert!(!breaker.allow());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record(false);
+        breaker.record(true);
+        breaker.record(false);
+
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_half_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record(false);
+        assert!(!breaker.allow());
+
+        thread::sleep(Duration::from_millis(30));
+
+        // only one probe is let through
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        // failed probe opens the circuit again
+        breaker.record(false);
+        assert!(!breaker.allow());
+
+        thread::sleep(Duration::from_millis(30));
+
+        assert!(breaker.allow());
+        breaker.record(true);
+        assert!(!breaker.is_open());
+        assert!(breaker.allow());
+    }
+
+    #[test]
+    fn test_released_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+
+        breaker.record(false);
+        thread::sleep(Duration::from_millis(30));
+
+        assert!(breaker.allow());
+        assert!(!breaker.allow());
+
+        breaker.release();
+        assert!(breaker.allow());
+    }
+}
@@ -1,41 +1,263 @@
-use ::measurement::Measurement;
+use ::measurement::{Measurement, Value};
 use ::serializer::Serializer;
 use ::client::{Precision, Client, Credentials, ClientError, ClientReadResult, ClientWriteResult};
-use ::hurl::{Hurl, Request, Method, Auth};
-use std::collections::HashMap;
-use futures::{Future, stream, Stream};
+use ::client::wal::WriteAheadLog;
+use ::client::breaker::CircuitBreaker;
+use ::schema::Schema;
+use ::influxql::{self, ContinuousQuery};
+#[cfg(feature = "serde_json")]
+use ::influxql::ContinuousQueryInfo;
+#[cfg(feature = "serde_json")]
+use serde_json;
+use ::serializer::line::{series_key, series_key_len};
+use ::hurl::{Hurl, Request, Response, Method, Auth, RESPONSE_TOO_LARGE};
+use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::marker::PhantomData;
+use std::slice;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::timer::{Delay, Timeout};
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, StartSend, stream, Stream};
+use futures::future::{Either, Loop};
 
 const MAX_BATCH: u16 = 5000;
 
+/// Tag and field names InfluxDB reserves: `time` is the timestamp column, so points using it are
+/// dropped, and `_field` and `_measurement` are used by the Flux data model.
+const RESERVED_NAMES: [&str; 3] = ["time", "_field", "_measurement"];
+
 pub enum WriteStatus {
     Success,
     CouldNotComplete,
 }
 
-// fixme
-pub struct Options {
-    pub max_batch: Option<u16>,
-    pub precision: Option<Precision>,
+/// Tells `HttpClient::write_with_callback` whether to send the remaining chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkControl {
+    Continue,
+    Abort
+}
+
+/// Tells `HttpClient::write_many` what to do when a chunk fails.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ErrorMode {
+    /// Fails with the error of the first failed chunk. Chunks are sent concurrently, so the other
+    /// ones may be written already; those still in flight are aborted.
+    #[default]
+    FailFast,
+    /// Sends all chunks, and fails with `ClientError::Chunks` listing outcomes of every chunk.
+    CollectErrors
+}
+
+/// Tells `HttpClient` what to do with NaN and infinite float fields, which line protocol cannot
+/// represent, so InfluxDB rejects the whole request containing them.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NonFinitePolicy {
+    /// Fails the write with `ClientError::Validation` without sending anything.
+    #[default]
+    Error,
+    /// Drops the field, and the measurement if no fields are left.
+    Skip,
+    /// Writes zero instead.
+    Zero
+}
+
+/// Write consistency level, supported by InfluxDB Enterprise clusters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Consistency {
+    Any,
+    One,
+    Quorum,
+    All
+}
+
+impl fmt::Display for Consistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Consistency::Any    => "any",
+            Consistency::One    => "one",
+            Consistency::Quorum => "quorum",
+            Consistency::All    => "all"
+        };
+
+        f.write_str(s)
+    }
+}
+
+/// Parameters of `HttpClient::write`. Parameters which are not set are omitted from the request,
+/// and the database and retention policy default to the ones of `Credentials`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions<'a> {
+    precision: Option<Precision>,
+    consistency: Option<Consistency>,
+    database: Option<&'a str>,
+    retention_policy: Option<&'a str>
+}
+
+impl<'a> WriteOptions<'a> {
+    fn with_precision(precision: Option<Precision>) -> WriteOptions<'a> {
+        WriteOptions {
+            precision,
+            ..WriteOptions::default()
+        }
+    }
+
+    /// Sets precision of written timestamps.
+    pub fn set_precision(&mut self, precision: Precision) -> &mut WriteOptions<'a> {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Sets precision of written timestamps from the token used in InfluxDB query params, like
+    /// `ms`, e.g. read from a config file. Returns error for unknown tokens, keeping the previous
+    /// precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::http::WriteOptions;
+    ///
+    /// let mut options = WriteOptions::default();
+    ///
+    /// assert!(options.set_precision_str("ms").is_ok());
+    /// assert!(options.set_precision_str("days").is_err());
+    /// ```
+    pub fn set_precision_str(&mut self, precision: &str) -> Result<&mut WriteOptions<'a>, String> {
+        self.precision = Some(precision.parse()?);
+        Ok(self)
+    }
+
+    /// Sets write consistency level.
+    pub fn set_consistency(&mut self, consistency: Consistency) -> &mut WriteOptions<'a> {
+        self.consistency = Some(consistency);
+        self
+    }
+
+    /// Sets database to write to instead of the one of `Credentials`. The retention policy of
+    /// `Credentials` is not used for another database.
+    pub fn set_database(&mut self, database: &'a str) -> &mut WriteOptions<'a> {
+        self.database = Some(database);
+        self
+    }
+
+    /// Sets retention policy to write to instead of the one of `Credentials`.
+    pub fn set_retention_policy(&mut self, retention_policy: &'a str) -> &mut WriteOptions<'a> {
+        self.retention_policy = Some(retention_policy);
+        self
+    }
+}
+
+/// Snapshot of request counters of `HttpClient`, returned by `HttpClient::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Stats {
+    /// Number of completed requests.
+    pub requests: u64,
+    /// Number of requests which failed, either to be sent or by the server response.
+    pub errors: u64,
+    /// Total time of completed requests, from the first poll until the response is read.
+    pub total_latency: Duration
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency_nanos: AtomicU64
+}
+
+impl Counters {
+    fn record(&self, latency: Duration, success: bool) {
+        let nanos = latency.as_secs().saturating_mul(1_000_000_000).saturating_add(u64::from(latency.subsec_nanos()));
+
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.latency_nanos.fetch_add(nanos, Ordering::Relaxed);
+
+        if !success {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Request let through by the circuit breaker. The probe is released if the request is dropped
+/// before its outcome is recorded, so a cancelled probe does not keep the circuit half-open.
+struct Probe {
+    breaker: Option<Arc<CircuitBreaker>>
+}
+
+impl Probe {
+    fn record(mut self, success: bool) {
+        if let Some(breaker) = self.breaker.take() {
+            breaker.record(success);
+        }
+    }
+}
 
-    pub epoch: Option<Precision>,
-    pub chunk_size: Option<u16>
+impl Drop for Probe {
+    fn drop(&mut self) {
+        if let Some(ref breaker) = self.breaker {
+            breaker.release();
+        }
+    }
 }
 
 pub struct HttpClient<'a> {
     credentials: Credentials<'a>,
-    serializer: Box<Serializer + Send + Sync>,
-    hurl: Box<Hurl + Send + Sync>,
+    serializer: Box<dyn Serializer + Send + Sync>,
+    hurl: Box<dyn Hurl + Send + Sync>,
     hosts: Vec<&'a str>,
-    pub max_batch: u16
+    wal: Option<Arc<WriteAheadLog>>,
+    schema: Option<Schema>,
+    headers: HashMap<String, String>,
+    max_series_key_len: Option<usize>,
+    max_tags: Option<usize>,
+    max_fields: Option<usize>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    error_mode: ErrorMode,
+    strict_precision: bool,
+    strict_names: bool,
+    group_by_series: bool,
+    prefix: Option<String>,
+    non_finite_policy: NonFinitePolicy,
+    counters: Arc<Counters>,
+    hash_tag: Option<String>,
+    request_ids: bool,
+    reject_duplicate_points: bool,
+    body_size_warning: Option<usize>,
+    combined_database: bool,
+    max_batch: u16
 }
 
 impl<'a> HttpClient<'a> {
-    pub fn new(credentials: Credentials<'a>, serializer: Box<Serializer + Send + Sync>, hurl: Box<Hurl + Send + Sync>) -> HttpClient<'a> {
+    pub fn new(credentials: Credentials<'a>, serializer: Box<dyn Serializer + Send + Sync>, hurl: Box<dyn Hurl + Send + Sync>) -> HttpClient<'a> {
         HttpClient {
-            credentials: credentials,
-            serializer: serializer,
-            hurl: hurl,
+            credentials,
+            serializer,
+            hurl,
             hosts: vec![],
+            wal: None,
+            schema: None,
+            headers: HashMap::new(),
+            max_series_key_len: None,
+            max_tags: None,
+            max_fields: None,
+            breaker: None,
+            error_mode: ErrorMode::default(),
+            strict_precision: false,
+            strict_names: false,
+            group_by_series: false,
+            prefix: None,
+            non_finite_policy: NonFinitePolicy::default(),
+            counters: Arc::new(Counters::default()),
+            hash_tag: None,
+            request_ids: false,
+            reject_duplicate_points: false,
+            body_size_warning: None,
+            combined_database: false,
             max_batch: MAX_BATCH
         }
     }
@@ -44,177 +266,3017 @@ impl<'a> HttpClient<'a> {
         self.hosts.push(host);
     }
 
-    fn get_host(&self) -> &'a str {
-        match self.hosts.first() {
-            Some(host) => host,
-            None => panic!("Could not get host")
+    /// Sets header sent with every request, overriding the default one with the same name.
+    ///
+    /// By default, writes are sent with `Content-Type: text/plain; charset=utf-8`, and queries are
+    /// sent with `Accept: application/json`.
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.insert(name.to_lowercase(), value.to_string());
+    }
+
+    /// Asks the server to send uncompressed responses with `Accept-Encoding: identity`.
+    ///
+    /// Useful behind proxies which compress responses twice or mangle compressed ones. Responses
+    /// are never decompressed by the client, so a server compressing them regardless of the header
+    /// produces undecodable bodies.
+    pub fn set_identity_encoding(&mut self) {
+        self.set_header("accept-encoding", "identity");
+    }
+
+    /// Sets schema which every written measurement is checked against. Writes with measurements
+    /// not conforming to the schema fail with `ClientError::Validation` without sending anything.
+    pub fn set_schema(&mut self, schema: Schema) {
+        self.schema = Some(schema);
+    }
+
+    /// Sets the limit of the series key length (measurement name and tags) in bytes. Writes with
+    /// measurements exceeding it fail with `ClientError::Validation` without sending anything,
+    /// instead of being rejected by the server. InfluxDB limits series keys to 65535 bytes.
+    pub fn set_max_series_key_len(&mut self, limit: usize) {
+        self.max_series_key_len = Some(limit);
+    }
+
+    /// Sets the limit of tags per measurement, to catch code accidentally blowing up series
+    /// cardinality. Writes with measurements exceeding it fail with `ClientError::Validation`
+    /// without sending anything.
+    pub fn set_max_tags(&mut self, limit: usize) {
+        self.max_tags = Some(limit);
+    }
+
+    /// Sets the limit of fields per measurement. Writes with measurements exceeding it fail with
+    /// `ClientError::Validation` without sending anything.
+    pub fn set_max_fields(&mut self, limit: usize) {
+        self.max_fields = Some(limit);
+    }
+
+    /// Sets the maximum number of measurements sent in one request. Defaults to 5000.
+    ///
+    /// Returns `ClientError::Validation` for zero, keeping the previous value.
+    pub fn set_max_batch(&mut self, max_batch: u16) -> Result<(), ClientError> {
+        if max_batch == 0 {
+            return Err(ClientError::Validation("max batch must be greater than zero".to_string()));
         }
+
+        self.max_batch = max_batch;
+        Ok(())
     }
-}
 
-impl<'a> Client for HttpClient<'a> {
-    fn query(&self, q: String, epoch: Option<Precision>) -> ClientReadResult {
+    /// Makes the client read `Credentials::database` as `database/retention_policy`, like
+    /// `mydb/weekly`, and send the retention policy as the `rp` parameter. Requests fail with
+    /// `ClientError::Validation` if the database is malformed. Disabled by default, so the
+    /// database is sent as is.
+    pub fn set_combined_database(&mut self, enabled: bool) {
+        self.combined_database = enabled;
+    }
+
+    /// Makes writes fail with `ClientError::Validation` without sending anything, when a timestamp
+    /// would be truncated by conversion to the write precision, like a nanosecond timestamp written
+    /// in milliseconds. Only timestamps with precision set are checked.
+    pub fn set_strict_precision(&mut self, strict: bool) {
+        self.strict_precision = strict;
+    }
+
+    /// Makes writes fail with `ClientError::Validation` without sending anything, when a tag or a
+    /// field uses a name reserved by InfluxDB, like `time`.
+    pub fn set_strict_names(&mut self, strict: bool) {
+        self.strict_names = strict;
+    }
+
+    /// Makes writes put measurements of the same series next to each other before chunking, which
+    /// InfluxDB writes more efficiently. Measurements of each series keep their order; the order
+    /// of series does not matter to InfluxDB.
+    ///
+    /// Applies to all writes; `WriteBatch` groups measurements collected until flush, and
+    /// `WriteSink` groups each batch it sends.
+    pub fn set_group_by_series(&mut self, enabled: bool) {
+        self.group_by_series = enabled;
+    }
+
+    /// Sets prefix prepended to the key of every written measurement, e.g. a tenant namespace. The
+    /// prefixed key is escaped as a whole, and measurements themselves are left unchanged.
+    ///
+    /// The series key limit applies to the prefixed key, while the schema is checked against the
+    /// keys measurements are created with.
+    pub fn set_prefix(&mut self, prefix: &str) {
+        self.prefix = Some(prefix.to_string());
+    }
+
+    /// Sets tag holding the hash of every written measurement, see `Measurement::content_hash`,
+    /// so that identical measurements sent more than once by at-least-once pipelines could be told
+    /// apart from distinct ones with the same timestamp.
+    ///
+    /// Every distinct hash is a new series, so the hash tag multiplies series cardinality by the
+    /// number of distinct points, and should only be used for low volume measurements.
+    pub fn set_hash_tag(&mut self, tag: &str) {
+        self.hash_tag = Some(tag.to_string());
+    }
+
+    /// Makes writes fail with `ClientError::Validation` without sending anything, when several
+    /// measurements of a write belong to the same series and have the same timestamp. InfluxDB
+    /// silently merges such points, so the fields of the earlier ones are overwritten.
+    pub fn set_reject_duplicate_points(&mut self, reject: bool) {
+        self.reject_duplicate_points = reject;
+    }
+
+    /// Makes the client log a warning with the `log` crate, when the body of a write request is
+    /// larger than `threshold` bytes, to catch accidentally giant writes.
+    pub fn set_body_size_warning(&mut self, threshold: usize) {
+        self.body_size_warning = Some(threshold);
+    }
+
+    /// Makes the client attach random UUID as `X-Request-Id` header to every request, so writes
+    /// could be correlated with server logs. Error messages of failed requests end with the
+    /// request id, the one echoed by the server if any.
+    pub fn set_request_ids(&mut self, enabled: bool) {
+        self.request_ids = enabled;
+    }
+
+    /// Sets how NaN and infinite float fields are written. Defaults to `NonFinitePolicy::Error`.
+    pub fn set_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.non_finite_policy = policy;
+    }
+
+    /// Sets how `write_many` handles failed chunks. Defaults to `ErrorMode::FailFast`.
+    pub fn set_error_mode(&mut self, mode: ErrorMode) {
+        self.error_mode = mode;
+    }
+
+    /// Sets circuit breaker. When it is open, writes and queries fail with
+    /// `ClientError::CircuitOpen` without sending anything. Communication errors and unexpected
+    /// responses are counted as failures.
+    pub fn set_circuit_breaker(&mut self, breaker: CircuitBreaker) {
+        self.breaker = Some(Arc::new(breaker));
+    }
+
+    /// Sets write-ahead log, where batches failed due to communication errors are stored to be
+    /// sent later with `replay`.
+    pub fn set_write_ahead_log(&mut self, wal: WriteAheadLog) {
+        self.wal = Some(Arc::new(wal));
+    }
+
+    /// Sends batches stored in the write-ahead log, and clears it if all of them were written.
+    ///
+    /// If some batch fails, the log is kept as is, so the next replay sends already written batches
+    /// again. InfluxDB overwrites points with the same series and timestamp, so this does not
+    /// produce duplicates.
+    pub fn replay(&self) -> ClientWriteResult {
+        let wal = match self.wal {
+            Some(ref wal) => wal.clone(),
+            None => return Box::new(future::ok(()))
+        };
+
+        let entries = match wal.entries() {
+            Ok(entries) => entries,
+            Err(e) => return Box::new(future::err(ClientError::from(e)))
+        };
+
         let host = self.get_host();
+        let futures = entries.into_iter().map(|entry| self.send_lines(host, entry.lines, &WriteOptions::with_precision(entry.precision)));
 
-        let mut query = HashMap::new();
-        query.insert("db", self.credentials.database.to_string());
-        query.insert("q", q);
+        Box::new(stream::futures_ordered(futures).for_each(|_| Ok(())).and_then(move |_| {
+            wal.clear().map_err(ClientError::from)
+        }))
+    }
 
-        if let Some(ref epoch) = epoch {
-            query.insert("epoch", epoch.to_string());
-        }
+    /// Writes raw line protocol, sending at most `max_batch` lines per request.
+    ///
+    /// InfluxDB does not accept comments, so lines starting with `#` are removed together with
+    /// blank lines, which allows writing annotated line protocol files as is.
+    pub fn write_lines(&self, lines: &str, precision: Option<Precision>) -> ClientWriteResult {
+        let lines: Vec<&str> = lines.lines()
+            .map(|line| line.trim_start())
+            .filter(|line| !line.trim_end().is_empty() && !line.starts_with('#'))
+            .collect();
+
+        let bodies = lines.chunks(self.max_batch as usize).map(|chunk| chunk.join("\n")).collect();
+
+        self.send_bodies(bodies, &WriteOptions::with_precision(precision))
+    }
+
+    /// Returns the difference between the server and the client clocks in seconds, positive when
+    /// the server clock is ahead. Timestamps written by a client with skewed clock land in
+    /// unexpected time ranges, so it is worth checking on startup.
+    ///
+    /// The server time is taken from the `Date` header of the `/ping` response, so the result is
+    /// only accurate to a second plus the request latency.
+    pub fn clock_skew(&self) -> Box<dyn Future<Item=i64, Error=ClientError> + Send> {
+        self.ping(|resp| {
+            let server = match resp.headers.get("date") {
+                Some(date) => parse_http_date(date).map_err(ClientError::Unexpected)?,
+                None => return Err(ClientError::Unexpected("ping response has no Date header".to_string()))
+            };
+
+            let client = match SystemTime::now().duration_since(UNIX_EPOCH) {
+                Ok(d) => d.as_secs() as i64,
+                Err(e) => -(e.duration().as_secs() as i64)
+            };
+
+            Ok(server - client)
+        })
+    }
+
+    /// Waits until the server responds to `/ping`, retrying with exponential backoff from 50ms up
+    /// to 5s, e.g. to hold writes on startup until InfluxDB is reachable. Fails with
+    /// `ClientError::Timeout` if the server is not ready within `timeout`.
+    ///
+    /// Backoff is implemented with tokio timer, so it requires a tokio runtime.
+    pub fn ready(&self, timeout: Duration) -> Box<dyn Future<Item=(), Error=ClientError> + Send + '_> {
+        let attempts = future::loop_fn(Duration::from_millis(50), move |backoff| {
+            self.ping(|_| Ok(())).then(move |res| {
+                match res {
+                    Ok(()) => Either::A(future::ok(Loop::Break(()))),
+                    Err(_) => Either::B(Delay::new(Instant::now() + backoff)
+                        .map_err(|e| ClientError::Unexpected(e.to_string()))
+                        .map(move |_| Loop::Continue((backoff * 2).min(Duration::from_secs(5)))))
+                }
+            })
+        });
+
+        Box::new(Timeout::new(attempts, timeout).map_err(|e| {
+            if e.is_elapsed() {
+                return ClientError::Timeout;
+            }
+
+            match e.into_inner() {
+                Some(e) => e,
+                None => ClientError::Unexpected("timer error".to_string())
+            }
+        }))
+    }
+
+    /// Sends `/ping` request, handling the response if it is successful.
+    fn ping<T, F>(&self, handle: F) -> Box<dyn Future<Item=T, Error=ClientError> + Send>
+        where T: Send + 'static, F: FnOnce(Response) -> Result<T, ClientError> + Send + 'static
+    {
+        let host = self.get_host();
 
         let request = Request {
-            url: &*{host.to_string() + "/query"},
+            url: &{host.to_string() + "/ping"},
             method: Method::GET,
-            auth: Some(Auth {
-                username: self.credentials.username,
-                password: self.credentials.password
-            }),
-            query: Some(query),
+            auth: self.auth(),
+            query: None,
+            headers: Some(self.headers(&[])),
             body: None
         };
 
-        Box::new(self.hurl.request(request).then(|res| {
-            match res {
-                Ok(ref resp) if resp.status == 200 => Ok(resp.to_string()),
-                Ok(ref resp) if resp.status == 400 => Err(ClientError::Syntax(resp.to_string())),
-                Ok(ref resp) => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", resp.status, resp.to_string()))),
-                Err(reason) => Err(ClientError::Communication(reason))
+        self.send(request, |resp| {
+            match resp.status {
+                200..=299 => handle(resp),
+                status => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", status, error_details(&resp))))
             }
-        }))
+        })
     }
 
-    fn write_one(&self, measurement: Measurement, precision: Option<Precision>) -> ClientWriteResult {
-        self.write_many(&[measurement], precision)
+    /// Runs administrative statement, like `SHOW DIAGNOSTICS` or `CREATE DATABASE`, without the
+    /// database parameter, which some server configurations reject for such statements.
+    pub fn query_admin(&self, q: String) -> ClientReadResult {
+        let mut query = HashMap::new();
+        query.insert("q", q);
+
+        self.send_query(query)
     }
 
-    fn write_many(&self, measurements: &[Measurement], precision: Option<Precision>) -> ClientWriteResult {
+    /// Sends request to the given path of the host as is, only adding authentication, and returns
+    /// the response body. It is an escape hatch for endpoints and options the client does not
+    /// support; any non-2xx response fails with `ClientError::Unexpected`.
+    pub fn query_raw(&self, method: Method, path: &str, params: HashMap<&str, String>, body: Option<String>) -> ClientReadResult {
         let host = self.get_host();
 
-        let futures = measurements.chunks(self.max_batch as usize).map(|chunk| {
-            let mut lines = Vec::new();
+        let request = Request {
+            url: &{host.to_string() + path},
+            method,
+            auth: self.auth(),
+            query: Some(params),
+            headers: None,
+            body
+        };
 
-            for measurement in chunk {
-                lines.push(self.serializer.serialize(measurement));
+        self.send(request, |resp| {
+            match resp.status {
+                200..=299 => Ok(resp.to_string()),
+                status => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", status, error_details(&resp))))
             }
+        })
+    }
 
-            let mut query = HashMap::new();
-            query.insert("db", self.credentials.database.to_string());
+    /// Runs query with `chunked=true`, so the server returns results in chunks of at most
+    /// `chunk_size` points, and returns the chunks in order, each being a JSON object. Fails with
+    /// `ClientError::Validation` for zero `chunk_size`.
+    ///
+    /// The response is still read as a whole, subject to the max response size of the hurl.
+    pub fn query_chunked(&self, q: String, epoch: Option<Precision>, chunk_size: u32) -> Box<dyn Future<Item=Vec<String>, Error=ClientError> + Send> {
+        if chunk_size == 0 {
+            return Box::new(future::err(ClientError::Validation("chunk size must be greater than zero".to_string())));
+        }
 
-            if let Some(ref precision) = precision {
-                query.insert("precision", precision.to_string());
-            }
+        let mut query = match self.database_params() {
+            Ok(query) => query,
+            Err(e) => return Box::new(future::err(e))
+        };
+        query.insert("q", q);
+        query.insert("chunked", "true".to_string());
+        query.insert("chunk_size", chunk_size.to_string());
 
-            let request = Request {
-                url: &*{host.to_string() + "/write"},
-                method: Method::POST,
-                auth: Some(Auth {
-                    username: self.credentials.username,
-                    password: self.credentials.password
-                }),
-                query: Some(query),
-                body: Some(lines.join("\n"))
-            };
+        if let Some(ref epoch) = epoch {
+            query.insert("epoch", epoch.to_string());
+        }
 
-            self.hurl.request(request).then(|res| {
-                match res {
-                    Ok(ref resp) if resp.status == 204 => Ok(()),
-                    Ok(ref resp) if resp.status == 200 => Err(ClientError::CouldNotComplete(resp.to_string())),
-                    Ok(ref resp) if resp.status == 400 => Err(ClientError::Syntax(resp.to_string())),
-                    Ok(ref resp) => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", resp.status, resp.to_string()))),
-                    Err(reason) => Err(ClientError::Communication(reason))
-                }
-            })
+        Box::new(self.send_query(query).map(|body| {
+            body.lines().filter(|line| !line.trim().is_empty()).map(|line| line.to_string()).collect()
+        }))
+    }
+
+    /// Runs several statements in one request. The response body contains one result per
+    /// statement, in the same order, with `statement_id` set to the index of the statement.
+    ///
+    /// Trailing semicolons of the statements are removed before joining them.
+    pub fn query_multi(&self, statements: &[String], epoch: Option<Precision>) -> ClientReadResult {
+        let joined: Vec<&str> = statements.iter().map(|s| s.trim().trim_end_matches(';')).collect();
+        self.query(joined.join("; "), epoch)
+    }
+
+    /// Runs several statements in one request like `query_multi`, and returns the result of every
+    /// statement, in the order of `statements`. Errors of single statements are kept in their
+    /// results, see `influxql::results_by_statement`.
+    #[cfg(feature = "serde_json")]
+    pub fn query_multi_results(&self, statements: &[String], epoch: Option<Precision>) -> Box<dyn Future<Item=Vec<serde_json::Value>, Error=ClientError> + Send> {
+        let count = statements.len();
+
+        Box::new(self.query_multi(statements, epoch).and_then(move |body| {
+            influxql::results_by_statement(&body, count).map_err(ClientError::Unexpected)
+        }))
+    }
+
+    /// Runs query over the nanosecond time range `[start, end)` as a sequence of queries over
+    /// windows of `window` nanoseconds, to avoid server timeouts on huge range scans. The query
+    /// must contain the `$timeFilter` placeholder, see `influxql::split_time_range`.
+    ///
+    /// Windows are queried one at a time, and the raw response bodies of the window queries are
+    /// returned in order, one per window; results are not merged.
+    pub fn query_time_range(&self, q: &str, start: i64, end: i64, window: i64, epoch: Option<Precision>) -> Box<dyn Future<Item=Vec<String>, Error=ClientError> + Send + '_> {
+        let queries = match influxql::split_time_range(q, start, end, window) {
+            Ok(queries) => queries,
+            Err(e) => return Box::new(future::err(ClientError::Validation(e)))
+        };
+
+        Box::new(stream::iter_ok(queries).and_then(move |q| self.query(q, epoch)).collect())
+    }
+
+    /// Runs query page by page, see `influxql::paginate`, and streams response bodies of the
+    /// pages until a page has no series, or the `LIMIT` of the query is reached. Pages are
+    /// requested one at a time, so results are not all held in memory. The stream fails with
+    /// `ClientError::CouldNotComplete` if a page has a statement error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate futures;
+    /// extern crate influent;
+    ///
+    /// use futures::Stream;
+    /// use influent::create_client;
+    /// use influent::client::Credentials;
+    ///
+    /// # fn main() {
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb"
+    /// };
+    ///
+    /// let client = create_client(credentials, vec!["http://localhost:8086"]);
+    ///
+    /// let pages = client.query_pages("SELECT * FROM cpu", 10000, None).for_each(|page| {
+    ///     println!("{}", page);
+    ///     Ok(())
+    /// });
+    /// # }
+    /// ```
+    pub fn query_pages(&self, q: &str, page_size: u64, epoch: Option<Precision>) -> Box<dyn Stream<Item=String, Error=ClientError> + Send + '_> {
+        let pages = match influxql::paginate(q, page_size) {
+            Ok(pages) => pages,
+            Err(e) => return Box::new(stream::once(Err(ClientError::Validation(e))))
+        };
+
+        let bodies = stream::unfold(pages, move |mut pages| {
+            pages.next().map(|q| self.query(q, epoch).map(move |body| (body, pages)))
         });
 
-        Box::new(stream::futures_ordered(futures).for_each(|_| Ok(())))
+        Box::new(bodies.take_while(|body| {
+            match statement_error(body) {
+                Some(error) => Err(ClientError::CouldNotComplete(error)),
+                None => has_series(body).map_err(ClientError::Unexpected)
+            }
+        }))
     }
-}
 
+    /// Runs statement not returning data, like `CREATE DATABASE` or `DROP MEASUREMENT`.
+    ///
+    /// InfluxDB reports statement errors in the body of `200 OK` responses, so the future fails
+    /// with `ClientError::CouldNotComplete` holding the error, when the body has one.
+    pub fn execute(&self, statement: String) -> ClientWriteResult {
+        Box::new(self.query(statement, None).and_then(|body| {
+            match statement_error(&body) {
+                Some(error) => Err(ClientError::CouldNotComplete(error)),
+                None => Ok(())
+            }
+        }))
+    }
 
+    /// Creates continuous query.
+    pub fn create_continuous_query(&self, cq: &ContinuousQuery) -> ClientReadResult {
+        self.query(cq.create_statement(), None)
+    }
 
-#[cfg(test)]
-mod tests {
-    use ::serializer::Serializer;
-    use ::client::{Client};
-    use super::HttpClient;
-    use ::client::{Credentials, Precision};
-    use ::hurl::{Hurl, Request, Response, HurlResult};
-    use ::measurement::Measurement;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use ::futures::{self, Future};
+    /// Drops continuous query.
+    pub fn drop_continuous_query(&self, cq: &ContinuousQuery) -> ClientReadResult {
+        self.query(cq.drop_statement(), None)
+    }
 
-    struct MockSerializer {
-        serialize_count: AtomicUsize,
+    /// Lists continuous queries of all databases.
+    pub fn show_continuous_queries(&self) -> ClientReadResult {
+        self.query("SHOW CONTINUOUS QUERIES".to_string(), None)
     }
 
-    impl MockSerializer {
-        fn new() -> MockSerializer {
-            MockSerializer {
-                serialize_count: AtomicUsize::new(0),
+    /// Lists continuous queries of all databases like `show_continuous_queries`, parsing the
+    /// response. Fails with `ClientError::CouldNotComplete` if the server reports an error.
+    #[cfg(feature = "serde_json")]
+    pub fn list_continuous_queries(&self) -> Box<dyn Future<Item=Vec<ContinuousQueryInfo>, Error=ClientError> + Send> {
+        Box::new(self.show_continuous_queries().and_then(|body| {
+            if let Some(error) = statement_error(&body) {
+                return Err(ClientError::CouldNotComplete(error));
             }
+
+            influxql::parse_continuous_queries(&body).map_err(ClientError::Unexpected)
+        }))
+    }
+
+    /// Writes measurements with given options.
+    ///
+    /// Writes to a database or retention policy other than the one of `Credentials` are not
+    /// logged to the write-ahead log, since it is replayed to the default one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::create_client;
+    /// use influent::client::{Credentials, Precision};
+    /// use influent::client::http::{Consistency, WriteOptions};
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb"
+    /// };
+    ///
+    /// let client = create_client(credentials, vec!["http://localhost:8086"]);
+    ///
+    /// let mut options = WriteOptions::default();
+    /// options.set_precision(Precision::Seconds).set_consistency(Consistency::Quorum).set_retention_policy("weekly");
+    ///
+    /// let mut measurement = Measurement::new("key");
+    /// measurement.add_field("some_field", Value::Integer(1));
+    ///
+    /// let write = client.write(&[measurement], &options);
+    /// ```
+    pub fn write(&self, measurements: &[Measurement], options: &WriteOptions) -> ClientWriteResult {
+        match self.serialize_bodies(measurements, &options.precision) {
+            Ok(bodies) => self.send_bodies(bodies, options),
+            Err(e) => Box::new(future::err(e))
         }
     }
 
-    impl Serializer for MockSerializer {
-        fn serialize(&self, measurement: &Measurement) -> String {
-            println!("serializing: {:?}", measurement);
-            self.serialize_count.fetch_add(1, Ordering::SeqCst);
-            "serialized".to_string()
+    /// Writes measurement like `write_one`, stamping it with the current time first, unless it
+    /// already has a timestamp.
+    pub fn write_one_now(&self, mut measurement: Measurement, precision: Option<Precision>) -> ClientWriteResult {
+        if measurement.timestamp.is_none() {
+            measurement.set_timestamp_now();
         }
+
+        self.write_one(measurement, precision)
     }
 
-    struct MockHurl {
-        request_count: AtomicUsize,
-        result: Box<(Fn() -> HurlResult) + Send + Sync>
+    /// Writes measurements to several targets, e.g. databases, each described by its options, and
+    /// returns outcomes of the targets in order. The returned future never fails.
+    ///
+    /// Targets are written independently: InfluxDB has no transactions, so there is no rollback
+    /// and a failed target leaves the others written. Callers needing all-or-nothing semantics
+    /// have to compensate, e.g. by retrying the failed targets.
+    pub fn write_fanout(&self, measurements: &[Measurement], targets: &[WriteOptions]) -> Box<dyn Future<Item=Vec<Result<(), ClientError>>, Error=ClientError> + Send> {
+        let futures = targets.iter().map(|options| self.write(measurements, options).then(Ok::<_, ClientError>));
+
+        Box::new(stream::futures_ordered(futures).collect())
     }
 
-    impl MockHurl {
-        fn new(result: Box<(Fn() -> HurlResult) + Send + Sync>) -> MockHurl {
-            MockHurl {
-                request_count: AtomicUsize::new(0),
-                result: result
-            }
+    /// Writes measurements like `write_many`, and also returns the line protocol sent in each
+    /// request, e.g. for audit logging. No lines are returned when measurements fail validation.
+    pub fn write_many_with_lines(&self, measurements: &[Measurement], precision: Option<Precision>) -> (ClientWriteResult, Vec<String>) {
+        match self.serialize_bodies(measurements, &precision) {
+            Ok(bodies) => (self.send_bodies(bodies.clone(), &WriteOptions::with_precision(precision)), bodies),
+            Err(e) => (Box::new(future::err(e)), vec![])
         }
     }
 
-    impl Hurl for MockHurl {
-        fn request(&self, req: Request) -> HurlResult {
-            println!("sending: {:?}", req);
-            self.request_count.fetch_add(1, Ordering::SeqCst);
-            let ref f = self.result;
-            f()
-        }
+    /// Validates measurements and serializes them into request bodies of `max_batch` measurements.
+    fn serialize_bodies(&self, measurements: &[Measurement], precision: &Option<Precision>) -> Result<Vec<String>, ClientError> {
+        self.validate(measurements, precision)?;
+
+        Ok(self.chunk_bodies(measurements, precision))
     }
 
-    fn before<'a>(result: Box<(Fn() -> HurlResult) + Send + Sync>) -> HttpClient<'a> {
-        let credentials = Credentials {
-            username: "gobwas",
-            password: "1234",
-            database: "test"
+    /// Serializes measurements into request bodies of `max_batch` measurements, grouping them by
+    /// series first if enabled.
+    fn chunk_bodies(&self, measurements: &[Measurement], precision: &Option<Precision>) -> Vec<String> {
+        let grouped;
+        let measurements = if self.group_by_series {
+            grouped = group_by_series(measurements, series_key);
+            &grouped[..]
+        } else {
+            measurements
         };
 
-        let serializer = MockSerializer::new();
-        let hurl = MockHurl::new(result);
+        measurements.chunks(self.max_batch as usize)
+            .map(|chunk| self.serialize_chunk(chunk, precision))
+            .collect()
+    }
 
-        HttpClient::new(credentials, Box::new(serializer), Box::new(hurl))
+    /// Serializes measurement into line, paired with its series key if writes are grouped by series.
+    fn keyed_line(&self, measurement: &Measurement, precision: &Option<Precision>) -> (String, String) {
+        let key = if self.group_by_series { series_key(measurement) } else { String::new() };
+
+        (key, self.serialize_chunk(slice::from_ref(measurement), precision))
     }
 
-    #[test]
-    fn test_write_one() {
-        let mut client = before(Box::new(|| Box::new(futures::future::ok(Response { status: 204, body: "Ok".to_string() }))));
-        client.add_host("http://localhost:8086");
-        ::tokio::run(client.write_one(Measurement::new("key"), Some(Precision::Nanoseconds)).map_err(|e| panic!(e)));
+    /// Joins lines returned by `keyed_line` into request bodies of `max_batch` lines, grouping
+    /// them by series first if enabled.
+    fn line_bodies(&self, lines: &[(String, String)]) -> Vec<String> {
+        let grouped;
+        let lines = if self.group_by_series {
+            grouped = group_by_series(lines, |line| line.0.clone());
+            &grouped[..]
+        } else {
+            lines
+        };
+
+        lines.chunks(self.max_batch as usize)
+            .map(|chunk| chunk.iter().map(|line| &line.1[..]).collect::<Vec<_>>().join("\n"))
+            .collect()
     }
 
-    #[test]
-    fn test_write_many() {
-        let mut client = before(Box::new(|| Box::new(futures::future::ok(Response { status: 204, body: "Ok".to_string() }))));
-        client.add_host("http://localhost:8086");
-        assert!(client.write_many(&[Measurement::new("key")], Some(Precision::Nanoseconds)).wait().is_ok());
+    /// Writes measurements to the hosts chosen by `route`, e.g. by hash of a tag in sharded setups.
+    ///
+    /// Measurements are grouped by host, keeping their order, and each group is written in chunks
+    /// of `max_batch` measurements. Hosts returned by `route` do not need to be added to the client.
+    pub fn write_many_routed<F, H>(&self, measurements: &[Measurement], precision: Option<Precision>, route: F) -> ClientWriteResult
+        where F: Fn(&Measurement) -> H, H: Into<String>
+    {
+        if let Err(e) = self.validate(measurements, &precision) {
+            return Box::new(future::err(e));
+        }
+
+        let mut groups: Vec<(String, Vec<Measurement>)> = vec![];
+
+        for measurement in measurements {
+            let host = route(measurement).into();
+
+            match groups.iter().position(|group| group.0 == host) {
+                Some(i) => groups[i].1.push(measurement.clone()),
+                None => groups.push((host, vec![measurement.clone()]))
+            }
+        }
+
+        let options = WriteOptions::with_precision(precision);
+        let mut futures = vec![];
+
+        for (host, group) in &groups {
+            for body in self.chunk_bodies(group, &precision) {
+                futures.push(self.send_logged(host, body, &options));
+            }
+        }
+
+        self.join_chunks(futures)
     }
-}
 
+    /// Writes measurements like `write_many`, failing with `ClientError::Timeout` if the write is
+    /// not completed by the deadline. Requests still in flight at the deadline are cancelled.
+    ///
+    /// The deadline is implemented with tokio timer, so it requires a tokio runtime.
+    pub fn write_many_with_deadline(&self, measurements: &[Measurement], precision: Option<Precision>, deadline: Instant) -> ClientWriteResult {
+        Box::new(Timeout::new_at(self.write_many(measurements, precision), deadline).map_err(|e| {
+            if e.is_elapsed() {
+                return ClientError::Timeout;
+            }
+
+            match e.into_inner() {
+                Some(e) => e,
+                None => ClientError::Unexpected("timer error".to_string())
+            }
+        }))
+    }
+
+    fn get_host(&self) -> &'a str {
+        match self.hosts.first() {
+            Some(host) => host,
+            None => panic!("Could not get host")
+        }
+    }
+
+    /// Writes measurements chunk by chunk, calling `callback` with each chunk index and its result.
+    ///
+    /// Chunks are sent one after another, and the next one is sent only when callback returns
+    /// `ChunkControl::Continue`. Returning `ChunkControl::Abort` leaves the remaining chunks unsent.
+    /// Chunk errors are reported only to the callback, so the returned future fails only when the
+    /// measurements could not be validated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::create_client;
+    /// use influent::client::Credentials;
+    /// use influent::client::http::ChunkControl;
+    /// use influent::measurement::Measurement;
+    ///
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb"
+    /// };
+    ///
+    /// let client = create_client(credentials, vec!["http://localhost:8086"]);
+    ///
+    /// let write = client.write_with_callback(&[Measurement::new("key")], None, |index, result| {
+    ///     match result {
+    ///         Ok(()) => ChunkControl::Continue,
+    ///         Err(e) => {
+    ///             println!("chunk {} failed: {:?}", index, e);
+    ///             ChunkControl::Abort
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn write_with_callback<'b, F>(&'b self, measurements: &[Measurement], precision: Option<Precision>, callback: F) -> Box<dyn Future<Item=(), Error=ClientError> + Send + 'b>
+        where F: FnMut(usize, Result<(), ClientError>) -> ChunkControl + Send + 'b
+    {
+        let host = self.get_host();
+
+        if let Err(e) = self.validate(measurements, &precision) {
+            return Box::new(future::err(e));
+        }
+
+        let bodies = self.chunk_bodies(measurements, &precision);
 
+        let options = WriteOptions::with_precision(precision);
 
+        Box::new(future::loop_fn((bodies.into_iter().enumerate(), callback), move |(mut bodies, mut callback)| {
+            match bodies.next() {
+                Some((index, body)) => Either::A(self.send_logged(host, body, &options).then(move |res| {
+                    match callback(index, res) {
+                        ChunkControl::Continue => Ok(Loop::Continue((bodies, callback))),
+                        ChunkControl::Abort => Ok(Loop::Break(()))
+                    }
+                })),
+                None => Either::B(future::ok(Loop::Break(())))
+            }
+        }))
+    }
+
+    /// Returns `Sink` writing measurements sent into it in batches of `max_batch` measurements, so
+    /// that a stream of measurements could be forwarded into the client.
+    ///
+    /// Full batches are sent as soon as they are collected, and the rest is sent on flush or close.
+    /// While a batch is being sent, the sink accepts measurements until the next batch is full.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate futures;
+    /// extern crate influent;
+    ///
+    /// use futures::{stream, Stream};
+    /// use influent::create_client;
+    /// use influent::client::{ClientError, Credentials};
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// # fn main() {
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb"
+    /// };
+    ///
+    /// let client = create_client(credentials, vec!["http://localhost:8086"]);
+    ///
+    /// let measurements = (0..10).map(|i| {
+    ///     let mut measurement = Measurement::new("key");
+    ///     measurement.add_field("i", Value::Integer(i));
+    ///     measurement
+    /// });
+    ///
+    /// let write = stream::iter_ok::<_, ClientError>(measurements).forward(client.sink(None));
+    /// # }
+    /// ```
+    pub fn sink<'m>(&self, precision: Option<Precision>) -> WriteSink<'_, 'a, 'm> {
+        WriteSink {
+            client: self,
+            precision,
+            buffer: vec![],
+            in_flight: None,
+            item: PhantomData
+        }
+    }
+
+    /// Returns counters of requests sent by the client, e.g. to compute the error rate or the
+    /// mean latency. Requests rejected by the circuit breaker are not sent, so are not counted.
+    pub fn stats(&self) -> Stats {
+        Stats {
+            requests: self.counters.requests.load(Ordering::Relaxed),
+            errors: self.counters.errors.load(Ordering::Relaxed),
+            total_latency: Duration::from_nanos(self.counters.latency_nanos.load(Ordering::Relaxed))
+        }
+    }
+
+    /// Returns batch shared by several call sites, collecting their measurements to send them
+    /// together. The batch is sent when `max_size` measurements are collected, or on `flush`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::create_client;
+    /// use influent::client::Credentials;
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb"
+    /// };
+    ///
+    /// let client = create_client(credentials, vec!["http://localhost:8086"]);
+    /// let batch = client.batch(None, 1000);
+    ///
+    /// let mut measurement = Measurement::new("key");
+    /// measurement.add_field("some_field", Value::Integer(1));
+    ///
+    /// let enqueued = batch.enqueue(&[measurement]);
+    /// let flushed = batch.flush();
+    /// ```
+    pub fn batch(&self, precision: Option<Precision>, max_size: usize) -> WriteBatch<'_, 'a> {
+        WriteBatch {
+            client: self,
+            precision,
+            max_size,
+            lines: Mutex::new(vec![])
+        }
+    }
+
+    fn headers(&self, defaults: &[(&'static str, &str)]) -> HashMap<&str, String> {
+        let mut headers: HashMap<&str, String> = defaults.iter().map(|&(name, value)| (name, value.to_string())).collect();
+
+        for (name, value) in &self.headers {
+            headers.insert(name, value.clone());
+        }
+
+        headers
+    }
+
+    fn validate(&self, measurements: &[Measurement], precision: &Option<Precision>) -> Result<(), ClientError> {
+        if let Some(ref schema) = self.schema {
+            for measurement in measurements {
+                schema.validate(measurement).map_err(ClientError::Validation)?;
+            }
+        }
+
+        for measurement in measurements {
+            let tag_count = match self.hash_tag {
+                Some(ref tag) if !measurement.tags.contains_key(&tag[..]) => measurement.tag_count() + 1,
+                _ => measurement.tag_count()
+            };
+
+            match (self.max_tags, self.max_fields) {
+                (Some(limit), _) if tag_count > limit => {
+                    return Err(ClientError::Validation(format!("measurement \"{}\" has {} tags, which exceeds the limit of {} tags", measurement.key, tag_count, limit)));
+                },
+                (_, Some(limit)) if measurement.field_count() > limit => {
+                    return Err(ClientError::Validation(format!("measurement \"{}\" has {} fields, which exceeds the limit of {} fields", measurement.key, measurement.field_count(), limit)));
+                },
+                _ => {}
+            }
+        }
+
+        if let Some(limit) = self.max_series_key_len {
+            for measurement in measurements {
+                let len = self.with_written_series(measurement, series_key_len);
+
+                if len > limit {
+                    return Err(ClientError::Validation(format!("series key of measurement \"{}\" is {} bytes long, which exceeds the limit of {} bytes", measurement.key, len, limit)));
+                }
+            }
+        }
+
+        if self.non_finite_policy == NonFinitePolicy::Error {
+            for measurement in measurements {
+                for (field, value) in &measurement.fields {
+                    if is_non_finite(value) {
+                        return Err(ClientError::Validation(format!("field \"{}\" of measurement \"{}\" is not a finite number", field, measurement.key)));
+                    }
+                }
+            }
+        }
+
+        if self.strict_names {
+            for measurement in measurements {
+                let names = measurement.tags.keys().chain(measurement.fields.keys());
+
+                for name in names {
+                    if RESERVED_NAMES.contains(&&name[..]) {
+                        return Err(ClientError::Validation(format!("measurement \"{}\" uses reserved name \"{}\"", measurement.key, name)));
+                    }
+                }
+            }
+        }
+
+        if self.reject_duplicate_points {
+            let precision = precision.unwrap_or(Precision::Nanoseconds);
+            let mut points = HashSet::new();
+
+            for measurement in measurements {
+                let timestamp = match measurement.timestamp {
+                    Some(timestamp) => measurement.precision.unwrap_or(precision).convert(timestamp, Precision::Nanoseconds),
+                    None => continue
+                };
+
+                let series_key = self.with_written_series(measurement, |measurement| measurement.series_key());
+
+                if !points.insert((series_key.clone(), timestamp)) {
+                    return Err(ClientError::Validation(format!("measurements of series \"{}\" have the same timestamp {}, so InfluxDB would merge them", series_key, timestamp)));
+                }
+            }
+        }
+
+        if self.strict_precision {
+            let precision = precision.unwrap_or(Precision::Nanoseconds);
+
+            for measurement in measurements {
+                if let (Some(timestamp), Some(p)) = (measurement.timestamp, measurement.precision) {
+                    if precision.convert(p.convert(timestamp, precision), p) != timestamp {
+                        return Err(ClientError::Validation(format!("timestamp {} of measurement \"{}\" in {} precision loses precision when written in {} precision", timestamp, measurement.key, p, precision)));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Calls `f` with the measurement as it is written, i.e. with the prefixed key and the hash tag.
+    fn with_written_series<R, F>(&self, measurement: &Measurement, f: F) -> R where F: FnOnce(&Measurement) -> R {
+        if self.prefix.is_none() && self.hash_tag.is_none() {
+            return f(measurement);
+        }
+
+        let key;
+        let hash;
+        let mut adjusted: Measurement = measurement.clone();
+
+        if let Some(ref tag) = self.hash_tag {
+            hash = format!("{:016x}", measurement.content_hash());
+            adjusted.add_tag(&tag[..], &hash[..]);
+        }
+
+        if let Some(ref prefix) = self.prefix {
+            key = format!("{}{}", prefix, measurement.key);
+            adjusted.key = &key;
+        }
+
+        f(&adjusted)
+    }
+
+    fn serialize_chunk(&self, chunk: &[Measurement], precision: &Option<Precision>) -> String {
+        // InfluxDB defaults to nanoseconds when precision is not given
+        let precision = precision.unwrap_or(Precision::Nanoseconds);
+        let len = chunk.iter().map(|measurement| self.serializer.serialized_len(measurement) + 1).sum();
+        let mut lines = String::with_capacity(len);
+
+        for measurement in chunk {
+            let timestamp = match (measurement.timestamp, measurement.precision) {
+                (Some(timestamp), Some(p)) if p != precision => Some(p.convert(timestamp, precision)),
+                _ => None
+            };
+
+            let non_finite = self.non_finite_policy != NonFinitePolicy::Error && measurement.fields.values().any(is_non_finite);
+
+            let line = self.with_written_series(measurement, |measurement| {
+                if timestamp.is_none() && !non_finite {
+                    return Some(self.serializer.serialize(measurement));
+                }
+
+                let mut adjusted: Measurement = measurement.clone();
+
+                if let Some(timestamp) = timestamp {
+                    adjusted.set_timestamp_with_precision(timestamp, precision);
+                }
+
+                if non_finite {
+                    match self.non_finite_policy {
+                        NonFinitePolicy::Skip => adjusted.fields.retain(|_, value| !is_non_finite(value)),
+                        _ => for value in adjusted.fields.values_mut().filter(|value| is_non_finite(value)) {
+                            *value = Value::Float(0.0);
+                        }
+                    }
+
+                    if adjusted.fields.is_empty() {
+                        return None;
+                    }
+                }
+
+                Some(self.serializer.serialize(&adjusted))
+            });
+
+            if let Some(line) = line {
+                if !lines.is_empty() {
+                    lines.push('\n');
+                }
+
+                lines.push_str(&line);
+            }
+        }
+
+        lines
+    }
+
+    fn send<T, F>(&self, mut request: Request, handle: F) -> Box<dyn Future<Item=T, Error=ClientError> + Send>
+        where T: Send + 'static, F: FnOnce(Response) -> Result<T, ClientError> + Send + 'static
+    {
+        let probe = match self.breaker {
+            Some(ref breaker) if !breaker.allow() => return Box::new(future::err(ClientError::CircuitOpen)),
+            Some(ref breaker) => Probe { breaker: Some(breaker.clone()) },
+            None => Probe { breaker: None }
+        };
+
+        let request_id = if self.request_ids {
+            let id = request_id();
+            request.headers.get_or_insert_with(HashMap::new).insert("x-request-id", id.clone());
+            Some(id)
+        } else {
+            None
+        };
+
+        let counters = self.counters.clone();
+        let request = self.hurl.request(request);
+
+        Box::new(future::lazy(move || Ok(Instant::now())).and_then(move |started| request.then(move |res| {
+            let too_large = matches!(res, Err(ref e) if e.starts_with(RESPONSE_TOO_LARGE));
+
+            let result = match request_id {
+                Some(id) => res.map_err(|e| with_request_id(hurl_error(e), &id)).and_then(|resp| {
+                    let id = resp.headers.get("x-request-id").cloned().unwrap_or(id);
+                    handle(resp).map_err(|e| with_request_id(e, &id))
+                }),
+                None => res.map_err(hurl_error).and_then(handle)
+            };
+
+            counters.record(started.elapsed(), result.is_ok());
+
+            match result {
+                // the server did respond, just too much
+                _ if too_large => probe.record(true),
+                Err(ClientError::Communication(_)) | Err(ClientError::Unexpected(_)) => probe.record(false),
+                _ => probe.record(true)
+            }
+
+            result
+        })))
+    }
+
+    fn send_query(&self, query: HashMap<&str, String>) -> ClientReadResult {
+        let host = self.get_host();
+
+        let request = Request {
+            url: &{host.to_string() + "/query"},
+            method: Method::GET,
+            auth: self.auth(),
+            query: Some(query),
+            headers: Some(self.headers(&[("accept", "application/json")])),
+            body: None
+        };
+
+        self.send(request, |resp| {
+            match resp.status {
+                200 => Ok(resp.to_string()),
+                400 => Err(ClientError::Syntax(error_details(&resp))),
+                status => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", status, error_details(&resp))))
+            }
+        })
+    }
+
+    fn auth(&self) -> Option<Auth<'a>> {
+        if !self.credentials.is_authenticated() {
+            return None;
+        }
+
+        Some(Auth {
+            username: self.credentials.username,
+            password: self.credentials.password
+        })
+    }
+
+    /// Returns `db` and `rp` query parameters.
+    fn database_params(&self) -> Result<HashMap<&'static str, String>, ClientError> {
+        let (database, retention_policy) = if self.combined_database {
+            self.credentials.database_and_retention_policy().map_err(ClientError::Validation)?
+        } else {
+            (self.credentials.database, None)
+        };
+
+        let mut query = HashMap::new();
+        query.insert("db", database.to_string());
+
+        if let Some(retention_policy) = retention_policy {
+            query.insert("rp", retention_policy.to_string());
+        }
+
+        Ok(query)
+    }
+
+    fn send_bodies(&self, bodies: Vec<String>, options: &WriteOptions) -> ClientWriteResult {
+        let host = self.get_host();
+
+        self.join_chunks(bodies.into_iter().map(|body| self.send_logged(host, body, options)))
+    }
+
+    /// Joins futures of chunk writes according to the error mode.
+    fn join_chunks<I>(&self, futures: I) -> ClientWriteResult where I: IntoIterator<Item=ClientWriteResult> {
+        match self.error_mode {
+            ErrorMode::FailFast => Box::new(stream::futures_ordered(futures).for_each(|_| Ok(()))),
+            ErrorMode::CollectErrors => {
+                let futures = futures.into_iter().map(|f| f.then(Ok::<_, ClientError>));
+
+                Box::new(stream::futures_ordered(futures).collect().and_then(|outcomes| {
+                    if outcomes.iter().all(|outcome| outcome.is_ok()) {
+                        Ok(())
+                    } else {
+                        Err(ClientError::Chunks(outcomes))
+                    }
+                }))
+            }
+        }
+    }
+
+    fn send_logged(&self, host: &str, body: String, options: &WriteOptions) -> ClientWriteResult {
+        let wal = match self.wal {
+            Some(ref wal) if options.database.is_none() && options.retention_policy.is_none() => wal.clone(),
+            _ => return self.send_lines(host, body, options)
+        };
+
+        let precision = options.precision;
+
+        Box::new(self.send_lines(host, body.clone(), options).or_else(move |e| {
+            match e {
+                ClientError::Communication(message) => match wal.append(precision, &body) {
+                    Ok(()) => Err(ClientError::Communication(message)),
+                    Err(wal_error) => Err(ClientError::Communication(format!("{}; could not append to the write-ahead log: {}", message, wal_error)))
+                },
+                e => Err(e)
+            }
+        }))
+    }
+
+    fn send_lines(&self, host: &str, body: String, options: &WriteOptions) -> ClientWriteResult {
+        // every point of the chunk was skipped
+        if body.is_empty() {
+            return Box::new(future::ok(()));
+        }
+
+        if let Some(threshold) = self.body_size_warning {
+            if body.len() > threshold {
+                let points = body.lines().count();
+                warn!("write request body of {} bytes with {} {} exceeds {} bytes", body.len(), points, if points == 1 { "point" } else { "points" }, threshold);
+            }
+        }
+
+        let mut query = match options.database {
+            // retention policy of the credentials belongs to their database
+            Some(database) => {
+                let mut query = HashMap::new();
+                query.insert("db", database.to_string());
+                query
+            },
+            None => match self.database_params() {
+                Ok(query) => query,
+                Err(e) => return Box::new(future::err(e))
+            }
+        };
+
+        if let Some(retention_policy) = options.retention_policy {
+            query.insert("rp", retention_policy.to_string());
+        }
+
+        if let Some(ref precision) = options.precision {
+            query.insert("precision", precision.to_string());
+        }
+
+        if let Some(ref consistency) = options.consistency {
+            query.insert("consistency", consistency.to_string());
+        }
+
+        let request = Request {
+            url: &{host.to_string() + "/write"},
+            method: Method::POST,
+            auth: self.auth(),
+            query: Some(query),
+            headers: Some(self.headers(&[("content-type", "text/plain; charset=utf-8")])),
+            body: Some(body)
+        };
+
+        self.send(request, |resp| {
+            match resp.status {
+                204 => Ok(()),
+                200 => Err(ClientError::CouldNotComplete(error_details(&resp))),
+                400 => Err(ClientError::Syntax(error_details(&resp))),
+                status => Err(ClientError::Unexpected(format!("Unexpected response. Status: {}; Body: \"{}\"", status, error_details(&resp))))
+            }
+        })
+    }
+}
+
+/// Returns items grouped by series key returned by `key`, in order of the first appearance of each series.
+fn group_by_series<T: Clone, F: Fn(&T) -> String>(items: &[T], key: F) -> Vec<T> {
+    let mut groups: Vec<(String, Vec<&T>)> = vec![];
+    let mut positions: HashMap<String, usize> = HashMap::new();
+
+    for item in items {
+        let key = key(item);
+
+        match positions.get(&key) {
+            Some(&i) => groups[i].1.push(item),
+            None => {
+                positions.insert(key.clone(), groups.len());
+                groups.push((key, vec![item]));
+            }
+        }
+    }
+
+    groups.into_iter().flat_map(|(_, group)| group.into_iter().cloned()).collect()
+}
+
+/// Batch of measurements returned by `HttpClient::batch`.
+pub struct WriteBatch<'c, 'a: 'c> {
+    client: &'c HttpClient<'a>,
+    precision: Option<Precision>,
+    max_size: usize,
+    // lines paired with series keys, see `HttpClient::keyed_line`
+    lines: Mutex<Vec<(String, String)>>
+}
+
+impl<'c, 'a: 'c> WriteBatch<'c, 'a> {
+    /// Adds measurements to the batch. The returned future sends the batch if it is full, and
+    /// completes right away otherwise; it fails if measurements do not pass validation.
+    pub fn enqueue(&self, measurements: &[Measurement]) -> ClientWriteResult {
+        if let Err(e) = self.client.validate(measurements, &self.precision) {
+            return Box::new(future::err(e));
+        }
+
+        let full = {
+            let mut lines = self.lines.lock().unwrap();
+            lines.extend(measurements.iter()
+                .map(|measurement| self.client.keyed_line(measurement, &self.precision))
+                .filter(|line| !line.1.is_empty()));
+            lines.len() >= self.max_size
+        };
+
+        if full {
+            self.flush()
+        } else {
+            Box::new(future::ok(()))
+        }
+    }
+
+    /// Sends all collected measurements, in chunks of `max_batch` measurements.
+    pub fn flush(&self) -> ClientWriteResult {
+        let lines = mem::take(&mut *self.lines.lock().unwrap());
+
+        self.client.send_bodies(self.client.line_bodies(&lines), &WriteOptions::with_precision(self.precision))
+    }
+
+    /// Returns the number of collected measurements.
+    pub fn len(&self) -> usize {
+        self.lines.lock().unwrap().len()
+    }
+
+    /// Returns whether the batch is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// `Sink` of measurements returned by `HttpClient::sink`.
+pub struct WriteSink<'c, 'a: 'c, 'm> {
+    client: &'c HttpClient<'a>,
+    precision: Option<Precision>,
+    // lines paired with series keys, see `HttpClient::keyed_line`
+    buffer: Vec<(String, String)>,
+    in_flight: Option<ClientWriteResult>,
+    item: PhantomData<fn(Measurement<'m>)>
+}
+
+impl<'c, 'a: 'c, 'm> Sink for WriteSink<'c, 'a, 'm> {
+    type SinkItem = Measurement<'m>;
+    type SinkError = ClientError;
+
+    fn start_send(&mut self, item: Measurement<'m>) -> StartSend<Measurement<'m>, ClientError> {
+        let max_batch = self.client.max_batch as usize;
+
+        if self.buffer.len() >= max_batch {
+            self.poll_complete()?;
+
+            if self.buffer.len() >= max_batch {
+                return Ok(AsyncSink::NotReady(item));
+            }
+        }
+
+        let measurements = slice::from_ref(&item);
+
+        self.client.validate(measurements, &self.precision)?;
+
+        let line = self.client.keyed_line(&item, &self.precision);
+
+        if !line.1.is_empty() {
+            self.buffer.push(line);
+        }
+
+        // send full batch right away, without waiting for flush
+        if self.buffer.len() >= max_batch {
+            self.poll_complete()?;
+        }
+
+        Ok(AsyncSink::Ready)
+    }
+
+    fn poll_complete(&mut self) -> Poll<(), ClientError> {
+        loop {
+            if let Some(mut in_flight) = self.in_flight.take() {
+                if let Async::NotReady = in_flight.poll()? {
+                    self.in_flight = Some(in_flight);
+                    return Ok(Async::NotReady);
+                }
+            }
+
+            if self.buffer.is_empty() {
+                return Ok(Async::Ready(()));
+            }
+
+            let len = self.buffer.len().min(self.client.max_batch as usize);
+            let lines = self.buffer.drain(..len).collect::<Vec<_>>();
+
+            self.in_flight = Some(self.client.send_bodies(self.client.line_bodies(&lines), &WriteOptions::with_precision(self.precision)));
+        }
+    }
+}
+
+/// Returns the first `error` of the query response, like `{"results":[{"statement_id":0,"error":"database not found: db"}]}`.
+#[cfg(feature = "serde_json")]
+fn statement_error(body: &str) -> Option<String> {
+    let response: serde_json::Value = serde_json::from_str(body).ok()?;
+
+    response["results"].as_array()?.iter()
+        .filter_map(|result| result["error"].as_str())
+        .next()
+        .map(|error| error.to_string())
+}
+
+/// Returns the first `error` of the query response, like `{"results":[{"statement_id":0,"error":"database not found: db"}]}`.
+#[cfg(not(feature = "serde_json"))]
+fn statement_error(body: &str) -> Option<String> {
+    const ID: &str = "\"statement_id\":";
+    const KEY: &str = ",\"error\":\"";
+
+    // InfluxDB writes statement_id first, and the error right after it, as results with errors
+    // have no series; so keys named "error" elsewhere, e.g. tags, are not matched
+    let mut rest = body;
+
+    while let Some(start) = rest.find(ID) {
+        rest = rest[start + ID.len()..].trim_start_matches(|c: char| c.is_ascii_digit());
+
+        if let Some(error) = rest.strip_prefix(KEY) {
+            return Some(read_json_string(error));
+        }
+    }
+
+    None
+}
+
+/// Reads JSON string up to its closing quote, which is not included in `s`, decoding escapes.
+#[cfg(not(feature = "serde_json"))]
+fn read_json_string(s: &str) -> String {
+    fn read_hex(chars: &mut ::std::str::Chars) -> Option<u32> {
+        let hex: String = chars.take(4).collect();
+        u32::from_str_radix(&hex, 16).ok()
+    }
+
+    let mut out = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => break,
+            '\\' => match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('t') => out.push('\t'),
+                Some('r') => out.push('\r'),
+                Some('b') => out.push('\u{8}'),
+                Some('f') => out.push('\u{c}'),
+                Some('u') => {
+                    let code = match read_hex(&mut chars) {
+                        // high surrogate, followed by the low one
+                        Some(high @ 0xD800..=0xDBFF) => match (chars.next(), chars.next(), read_hex(&mut chars)) {
+                            (Some('\\'), Some('u'), Some(low @ 0xDC00..=0xDFFF)) => Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)),
+                            _ => None
+                        },
+                        code => code
+                    };
+
+                    out.push(code.and_then(::std::char::from_u32).unwrap_or('\u{FFFD}'));
+                },
+                Some(c) => out.push(c),
+                None => break
+            },
+            c => out.push(c)
+        }
+    }
+
+    out
+}
+
+/// Generates random version 4 UUID.
+fn request_id() -> String {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+
+    // every RandomState has new random keys
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(nanos);
+    let hi = (hasher.finish() & !0xf000) | 0x4000;
+    hasher.write_u8(0);
+    let lo = (hasher.finish() & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000;
+
+    format!("{:08x}-{:04x}-{:04x}-{:04x}-{:012x}", hi >> 32, (hi >> 16) & 0xffff, hi & 0xffff, lo >> 48, lo & 0xffff_ffff_ffff)
+}
+
+/// Appends request id to the error message.
+fn with_request_id(e: ClientError, id: &str) -> ClientError {
+    let annotate = |message: String| format!("{} (request id: {})", message, id);
+
+    match e {
+        ClientError::CouldNotComplete(message) => ClientError::CouldNotComplete(annotate(message)),
+        ClientError::Communication(message) => ClientError::Communication(annotate(message)),
+        ClientError::Syntax(message) => ClientError::Syntax(annotate(message)),
+        ClientError::Unexpected(message) => ClientError::Unexpected(annotate(message)),
+        e => e
+    }
+}
+
+/// Returns whether query response has any series. Empty results have no series, like
+/// `{"results":[{"statement_id":0}]}`.
+#[cfg(feature = "serde_json")]
+fn has_series(body: &str) -> Result<bool, String> {
+    let response: serde_json::Value = serde_json::from_str(body).map_err(|e| format!("could not parse response: {}", e))?;
+    let results = response["results"].as_array().map(|results| &results[..]).unwrap_or(&[]);
+
+    Ok(results.iter().any(|result| result["series"].as_array().is_some_and(|series| !series.is_empty())))
+}
+
+#[cfg(not(feature = "serde_json"))]
+fn has_series(body: &str) -> Result<bool, String> {
+    Ok(body.contains("\"series\""))
+}
+
+fn hurl_error(message: String) -> ClientError {
+    if message.starts_with(RESPONSE_TOO_LARGE) {
+        ClientError::Unexpected(message)
+    } else {
+        ClientError::Communication(message)
+    }
+}
+
+fn is_non_finite(value: &Value) -> bool {
+    match *value {
+        Value::Float(f) => !f.is_finite(),
+        _ => false
+    }
+}
+
+/// Returns error details of the response. InfluxDB reports errors in the body, and sometimes only
+/// in the `X-Influxdb-Error` header.
+fn error_details(resp: &Response) -> String {
+    match resp.headers.get("x-influxdb-error") {
+        Some(error) if resp.body.is_empty() => error.clone(),
+        Some(error) if !resp.body.contains(&error[..]) => format!("{}; {}", resp.body, error),
+        _ => resp.body.clone()
+    }
+}
+
+/// Parses HTTP date, like `Sun, 06 Nov 1994 08:49:37 GMT`, to seconds since epoch.
+fn parse_http_date(date: &str) -> Result<i64, String> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let invalid = || format!("invalid HTTP date: \"{}\"", date);
+
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() != 6 || parts[5] != "GMT" {
+        return Err(invalid());
+    }
+
+    let day: i64 = parts[1].parse().map_err(|_| invalid())?;
+    let month = MONTHS.iter().position(|&m| m == parts[2]).ok_or_else(invalid)? as i64 + 1;
+    let year: i64 = parts[3].parse().map_err(|_| invalid())?;
+
+    let time: Vec<i64> = parts[4].split(':').map(|part| part.parse().map_err(|_| invalid())).collect::<Result<_, _>>()?;
+    if time.len() != 3 {
+        return Err(invalid());
+    }
+
+    // days since epoch of the proleptic Gregorian calendar date
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * m + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    Ok(days * 86_400 + time[0] * 3_600 + time[1] * 60 + time[2])
+}
+
+impl<'a> Client for HttpClient<'a> {
+    fn query(&self, q: String, epoch: Option<Precision>) -> ClientReadResult {
+        let mut query = match self.database_params() {
+            Ok(query) => query,
+            Err(e) => return Box::new(future::err(e))
+        };
+        query.insert("q", q);
+
+        if let Some(ref epoch) = epoch {
+            query.insert("epoch", epoch.to_string());
+        }
+
+        self.send_query(query)
+    }
+
+    fn write_one(&self, measurement: Measurement, precision: Option<Precision>) -> ClientWriteResult {
+        self.write_many(&[measurement], precision)
+    }
+
+    fn write_many(&self, measurements: &[Measurement], precision: Option<Precision>) -> ClientWriteResult {
+        self.write(measurements, &WriteOptions::with_precision(precision))
+    }
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use ::serializer::Serializer;
+    use ::serializer::line::LineSerializer;
+    use ::client::{Client};
+    use super::{HttpClient, ChunkControl, ErrorMode, NonFinitePolicy, WriteOptions, Consistency, parse_http_date, statement_error};
+    use ::client::wal::WriteAheadLog;
+    use ::client::breaker::CircuitBreaker;
+    use ::client::ClientError;
+    use ::schema::Schema;
+    use ::influxql::ContinuousQuery;
+    use ::client::{Credentials, Precision};
+    use ::hurl::{Hurl, Request, Response, Method, HurlResult, RESPONSE_TOO_LARGE};
+    use ::measurement::{Measurement, Value, FieldType};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::env;
+    use std::process;
+    use std::thread;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+    use tokio::runtime::current_thread::Runtime;
+    use ::futures::{self, Future, Stream};
+
+    struct MockSerializer {
+        serialize_count: AtomicUsize,
+    }
+
+    impl MockSerializer {
+        fn new() -> MockSerializer {
+            MockSerializer {
+                serialize_count: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Serializer for MockSerializer {
+        fn serialize(&self, measurement: &Measurement) -> String {
+            println!("serializing: {:?}", measurement);
+            self.serialize_count.fetch_add(1, Ordering::SeqCst);
+            "serialized".to_string()
+        }
+    }
+
+    struct MockHurl {
+        request_count: AtomicUsize,
+        result: Box<dyn Fn() -> HurlResult + Send + Sync>
+    }
+
+    impl MockHurl {
+        fn new(result: Box<dyn Fn() -> HurlResult + Send + Sync>) -> MockHurl {
+            MockHurl {
+                request_count: AtomicUsize::new(0),
+                result
+            }
+        }
+    }
+
+    impl Hurl for MockHurl {
+        fn request(&self, req: Request) -> HurlResult {
+            println!("sending: {:?}", req);
+            self.request_count.fetch_add(1, Ordering::SeqCst);
+            (self.result)()
+        }
+    }
+
+    fn before<'a>(result: Box<dyn Fn() -> HurlResult + Send + Sync>) -> HttpClient<'a> {
+        let credentials = Credentials {
+            username: "gobwas",
+            password: "1234",
+            database: "test"
+        };
+
+        let serializer = MockSerializer::new();
+        let hurl = MockHurl::new(result);
+
+        HttpClient::new(credentials, Box::new(serializer), Box::new(hurl))
+    }
+
+    #[derive(Debug)]
+    struct Sent {
+        url: String,
+        query: HashMap<String, String>,
+        headers: HashMap<String, String>,
+        authenticated: bool,
+        body: Option<String>
+    }
+
+    struct RecordingHurl {
+        sent: Arc<Mutex<Vec<Sent>>>
+    }
+
+    impl Hurl for RecordingHurl {
+        fn request(&self, req: Request) -> HurlResult {
+            let status = if req.url.ends_with("/query") { 200 } else { 204 };
+            self.sent.lock().unwrap().push(Sent {
+                url: req.url.to_string(),
+                query: req.query.unwrap_or_default().into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+                headers: req.headers.unwrap_or_default().into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
+                authenticated: req.auth.is_some(),
+                body: req.body
+            });
+            Box::new(futures::future::ok(Response { status, headers: HashMap::new(), body: String::new() }))
+        }
+    }
+
+    fn recording<'a>() -> (HttpClient<'a>, Arc<Mutex<Vec<Sent>>>) {
+        let credentials = Credentials {
+            username: "gobwas",
+            password: "1234",
+            database: "test"
+        };
+
+        let sent = Arc::new(Mutex::new(vec![]));
+        let hurl = RecordingHurl { sent: sent.clone() };
+
+        let mut client = HttpClient::new(credentials, Box::new(LineSerializer::new()), Box::new(hurl));
+        client.add_host("http://localhost:8086");
+
+        (client, sent)
+    }
+
+    #[test]
+    fn test_write_one() {
+        let mut client = before(Box::new(|| Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: "Ok".to_string() }))));
+        client.add_host("http://localhost:8086");
+        ::tokio::run(client.write_one(Measurement::new("key"), Some(Precision::Nanoseconds)).map_err(|e| panic!("{:?}", e)));
+    }
+
+    #[test]
+    fn test_write_with_callback() {
+        let mut client = before(Box::new(|| Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: "Ok".to_string() }))));
+        client.add_host("http://localhost:8086");
+        client.set_max_batch(1).unwrap();
+
+        let mut chunks = vec![];
+        let measurements = [Measurement::new("a"), Measurement::new("b"), Measurement::new("c")];
+        client.write_with_callback(&measurements, None, |index, result| {
+            assert!(result.is_ok());
+            chunks.push(index);
+            ChunkControl::Continue
+        }).wait().unwrap();
+
+        assert_eq!(vec![0, 1, 2], chunks);
+    }
+
+    #[test]
+    fn test_write_with_callback_abort() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let mut client = before(Box::new(move || {
+            match counter.fetch_add(1, Ordering::SeqCst) {
+                1 => Box::new(futures::future::ok(Response { status: 500, headers: HashMap::new(), body: "Oops".to_string() })),
+                _ => Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: "Ok".to_string() }))
+            }
+        }));
+        client.add_host("http://localhost:8086");
+        client.set_max_batch(1).unwrap();
+
+        let mut chunks = vec![];
+        let measurements = [Measurement::new("a"), Measurement::new("b"), Measurement::new("c")];
+        client.write_with_callback(&measurements, None, |index, result| {
+            chunks.push(index);
+            if result.is_ok() { ChunkControl::Continue } else { ChunkControl::Abort }
+        }).wait().unwrap();
+
+        assert_eq!(vec![0, 1], chunks);
+        assert_eq!(2, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_write_many() {
+        let mut client = before(Box::new(|| Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: "Ok".to_string() }))));
+        client.add_host("http://localhost:8086");
+        assert!(client.write_many(&[Measurement::new("key")], Some(Precision::Nanoseconds)).wait().is_ok());
+    }
+
+    #[test]
+    fn test_write_many_normalizes_precision() {
+        let (client, sent) = recording();
+
+        let mut seconds = Measurement::new("key");
+        seconds.add_field("value", Value::Integer(1));
+        seconds.set_timestamp_with_precision(1434055562, Precision::Seconds);
+
+        let mut nanoseconds = Measurement::new("key");
+        nanoseconds.add_field("value", Value::Integer(2));
+        nanoseconds.set_timestamp_with_precision(1434055562123456789, Precision::Nanoseconds);
+
+        let mut unspecified = Measurement::new("key");
+        unspecified.add_field("value", Value::Integer(3));
+        unspecified.set_timestamp(1434055562456);
+
+        client.write_many(&[seconds, nanoseconds, unspecified], Some(Precision::Milliseconds)).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!("http://localhost:8086/write", sent[0].url);
+        assert_eq!(Some("ms"), sent[0].query.get("precision").map(|s| &s[..]));
+        assert_eq!(Some("key value=1i 1434055562000\nkey value=2i 1434055562123\nkey value=3i 1434055562456"), sent[0].body.as_ref().map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_write_ahead_log() {
+        let path = env::temp_dir().join(format!("influent-client-wal-{}.lp", process::id()));
+        let wal = WriteAheadLog::new(path.clone(), 1024);
+        wal.clear().unwrap();
+
+        let online = Arc::new(AtomicBool::new(false));
+        let sent = Arc::new(Mutex::new(vec![]));
+
+        let credentials = Credentials {
+            username: "gobwas",
+            password: "1234",
+            database: "test"
+        };
+
+        let hurl = MockHurl::new(Box::new({
+            let online = online.clone();
+            let sent = sent.clone();
+            move || -> HurlResult {
+                if online.load(Ordering::SeqCst) {
+                    sent.lock().unwrap().push(());
+                    Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: String::new() }))
+                } else {
+                    Box::new(futures::future::err("connection refused".to_string()))
+                }
+            }
+        }));
+
+        let mut client = HttpClient::new(credentials, Box::new(LineSerializer::new()), Box::new(hurl));
+        client.add_host("http://localhost:8086");
+        client.set_write_ahead_log(wal);
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+        assert!(client.write_one(measurement, Some(Precision::Seconds)).wait().is_err());
+
+        let entries = WriteAheadLog::new(path.clone(), 1024).entries().unwrap();
+        assert_eq!(1, entries.len());
+        assert_eq!(Some(Precision::Seconds), entries[0].precision);
+        assert_eq!("key value=1i", entries[0].lines);
+
+        online.store(true, Ordering::SeqCst);
+        client.replay().wait().unwrap();
+
+        assert_eq!(1, sent.lock().unwrap().len());
+        assert!(WriteAheadLog::new(path, 1024).entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_write_ahead_log_append_failed() {
+        let path = env::temp_dir().join(format!("influent-client-wal-full-{}.lp", process::id()));
+        let wal = WriteAheadLog::new(path.clone(), 1);
+        wal.clear().unwrap();
+
+        let mut client = before(Box::new(|| -> HurlResult {
+            Box::new(futures::future::err("connection refused".to_string()))
+        }));
+        client.add_host("http://localhost:8086");
+        client.set_write_ahead_log(wal);
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Communication(message)) => {
+                assert!(message.starts_with("connection refused; could not append to the write-ahead log"), "{}", message);
+            },
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_response_too_large() {
+        let path = env::temp_dir().join(format!("influent-client-too-large-{}.lp", process::id()));
+        let wal = WriteAheadLog::new(path.clone(), 1024);
+        wal.clear().unwrap();
+
+        let mut client = before(Box::new(|| -> HurlResult {
+            Box::new(futures::future::err(format!("{} of 16 bytes. Status: 200", RESPONSE_TOO_LARGE)))
+        }));
+        client.add_host("http://localhost:8086");
+        client.set_write_ahead_log(wal);
+        client.set_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Unexpected(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert!(WriteAheadLog::new(path, 1024).entries().unwrap().is_empty());
+        assert!(!client.breaker.as_ref().unwrap().is_open());
+    }
+
+    struct DropFlag(Arc<AtomicBool>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_drop_cancels_request() {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let mut client = before(Box::new({
+            let dropped = dropped.clone();
+            move || -> HurlResult {
+                let flag = DropFlag(dropped.clone());
+                Box::new(futures::future::empty().map(move |resp| {
+                    let _ = &flag;
+                    resp
+                }))
+            }
+        }));
+        client.add_host("http://localhost:8086");
+
+        let write = client.write_one(Measurement::new("key"), None);
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        // polls the write once, and drops it as soon as the other future completes
+        assert!(write.select(futures::future::ok(())).wait().is_ok());
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_write_many_schema_mismatch() {
+        let (mut client, sent) = recording();
+
+        let mut schema = Schema::new();
+        schema.add_field("key", "value", FieldType::Float);
+        client.set_schema(schema);
+
+        let mut conforming = Measurement::new("key");
+        conforming.add_field("value", Value::Float(1.0));
+        assert!(client.write_one(conforming, None).wait().is_ok());
+
+        let mut drifted = Measurement::new("key");
+        drifted.add_field("value", Value::Integer(1));
+        match client.write_one(drifted, None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_default_headers() {
+        let (client, sent) = recording();
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        client.query("show databases".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("text/plain; charset=utf-8"), sent[0].headers.get("content-type").map(|s| &s[..]));
+        assert_eq!(None, sent[0].headers.get("accept"));
+        assert_eq!(Some("application/json"), sent[1].headers.get("accept").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_set_header() {
+        let (mut client, sent) = recording();
+        client.set_header("Content-Type", "text/plain");
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("text/plain"), sent[0].headers.get("content-type").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_set_identity_encoding() {
+        let (mut client, sent) = recording();
+        client.set_identity_encoding();
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        client.query("SHOW DATABASES".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("identity"), sent[0].headers.get("accept-encoding").map(|s| &s[..]));
+        assert_eq!(Some("identity"), sent[1].headers.get("accept-encoding").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_write_many_series_key_limit() {
+        let (mut client, sent) = recording();
+        client.set_max_series_key_len(16);
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_tag("host", "a");
+        assert!(client.write_one(measurement, None).wait().is_ok());
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_tag("host", "a");
+        measurement.add_tag("region", "b");
+        match client.write_one(measurement, None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_write_many_tag_and_field_limits() {
+        let (mut client, sent) = recording();
+        client.set_max_tags(1);
+        client.set_max_fields(1);
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_tag("host", "a");
+        measurement.add_field("usage", Value::Float(0.5));
+        assert!(client.write_one(measurement.clone(), None).wait().is_ok());
+
+        let mut tagged = measurement.clone();
+        tagged.add_tag("region", "b");
+        match client.write_one(tagged, None).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("\"cpu\" has 2 tags"), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        measurement.add_field("idle", Value::Float(0.5));
+        match client.write_one(measurement, None).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("\"cpu\" has 2 fields"), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_continuous_queries() {
+        let (client, sent) = recording();
+        let cq = ContinuousQuery::new("cq", "test", "SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h)");
+
+        client.create_continuous_query(&cq).wait().unwrap();
+        client.show_continuous_queries().wait().unwrap();
+        client.drop_continuous_query(&cq).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some(&cq.create_statement()), sent[0].query.get("q"));
+        assert_eq!(Some("SHOW CONTINUOUS QUERIES"), sent[1].query.get("q").map(|s| &s[..]));
+        assert_eq!(Some(&cq.drop_statement()), sent[2].query.get("q"));
+    }
+
+    #[test]
+    fn test_error_header() {
+        let mut client = before(Box::new(|| {
+            let mut headers = HashMap::new();
+            headers.insert("x-influxdb-error".to_string(), "timeout".to_string());
+            Box::new(futures::future::ok(Response { status: 500, headers, body: String::new() }))
+        }));
+        client.add_host("http://localhost:8086");
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Unexpected(message)) => assert!(message.contains("timeout"), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_query_multi() {
+        let (client, sent) = recording();
+
+        client.query_multi(&["SHOW DATABASES;".to_string(), " SELECT * FROM cpu ".to_string()], None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(Some("SHOW DATABASES; SELECT * FROM cpu"), sent[0].query.get("q").map(|s| &s[..]));
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_query_multi_results() {
+        let client = responding(r#"{"results":[{"statement_id":1,"series":[{"name":"cpu"}]},{"statement_id":0,"series":[{"name":"databases"}]}]}"#);
+
+        let results = client.query_multi_results(&["SHOW DATABASES".to_string(), "SELECT * FROM cpu".to_string()], None).wait().unwrap();
+        assert_eq!("databases", results[0]["series"][0]["name"]);
+        assert_eq!("cpu", results[1]["series"][0]["name"]);
+
+        match client.query_multi_results(&["SHOW DATABASES".to_string()], None).wait() {
+            Err(ClientError::Unexpected(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let failing = Arc::new(AtomicBool::new(true));
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let mut client = {
+            let failing = failing.clone();
+            let requests = requests.clone();
+
+            before(Box::new(move || {
+                requests.fetch_add(1, Ordering::SeqCst);
+
+                if failing.load(Ordering::SeqCst) {
+                    Box::new(futures::future::err("connection refused".to_string()))
+                } else {
+                    Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: String::new() }))
+                }
+            }))
+        };
+        client.add_host("http://localhost:8086");
+        client.set_circuit_breaker(CircuitBreaker::new(2, Duration::from_millis(50)));
+
+        for _ in 0..2 {
+            match client.write_one(Measurement::new("key"), None).wait() {
+                Err(ClientError::Communication(_)) => {},
+                res => panic!("unexpected result: {:?}", res)
+            }
+        }
+
+        // open circuit does not send anything
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::CircuitOpen) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        match client.query("SHOW DATABASES".to_string(), None).wait() {
+            Err(ClientError::CircuitOpen) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert_eq!(2, requests.load(Ordering::SeqCst));
+
+        // successful probe closes the circuit
+        failing.store(false, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(60));
+
+        assert!(client.write_one(Measurement::new("key"), None).wait().is_ok());
+        assert!(client.write_one(Measurement::new("key"), None).wait().is_ok());
+        assert_eq!(4, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_write_lines_strips_comments() {
+        let (client, sent) = recording();
+
+        let file = "# DML\n# CONTEXT-DATABASE: test\n\ncpu value=1i 1\n  # inline comment\nmem,host=a value=2i 2\n\n";
+        client.write_lines(file, Some(Precision::Seconds)).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(Some("cpu value=1i 1\nmem,host=a value=2i 2".to_string()), sent[0].body);
+        assert_eq!(Some("s"), sent[0].query.get("precision").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_circuit_breaker_dropped_probe() {
+        let failing = Arc::new(AtomicBool::new(true));
+
+        let mut client = {
+            let failing = failing.clone();
+
+            before(Box::new(move || -> HurlResult {
+                if failing.load(Ordering::SeqCst) {
+                    Box::new(futures::future::err("connection refused".to_string()))
+                } else {
+                    Box::new(futures::future::empty())
+                }
+            }))
+        };
+        client.add_host("http://localhost:8086");
+        client.set_circuit_breaker(CircuitBreaker::new(1, Duration::from_millis(20)));
+
+        assert!(client.write_one(Measurement::new("key"), None).wait().is_err());
+
+        failing.store(false, Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(30));
+
+        // the probe never completes and is dropped, e.g. by a deadline
+        drop(client.write_one(Measurement::new("key"), None));
+
+        assert!(client.breaker.as_ref().unwrap().allow());
+    }
+
+    fn failing_second() -> (HttpClient<'static>, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let mut client = {
+            let requests = requests.clone();
+
+            before(Box::new(move || {
+                match requests.fetch_add(1, Ordering::SeqCst) {
+                    1 => Box::new(futures::future::ok(Response { status: 400, headers: HashMap::new(), body: "bad".to_string() })),
+                    _ => Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: String::new() }))
+                }
+            }))
+        };
+        client.add_host("http://localhost:8086");
+        client.set_max_batch(1).unwrap();
+
+        (client, requests)
+    }
+
+    #[test]
+    fn test_write_many_fail_fast() {
+        let (client, _) = failing_second();
+
+        match client.write_many(&[Measurement::new("a"), Measurement::new("b"), Measurement::new("c")], None).wait() {
+            Err(ClientError::Syntax(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_write_many_collect_errors() {
+        let (mut client, _) = failing_second();
+        client.set_error_mode(ErrorMode::CollectErrors);
+
+        match client.write_many(&[Measurement::new("a"), Measurement::new("b"), Measurement::new("c")], None).wait() {
+            Err(ClientError::Chunks(outcomes)) => {
+                assert_eq!(3, outcomes.len());
+                assert!(outcomes[0].is_ok());
+                assert!(matches!(outcomes[1], Err(ClientError::Syntax(_))));
+                assert!(outcomes[2].is_ok());
+            },
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        let (mut client, _) = failing_second();
+        client.set_error_mode(ErrorMode::CollectErrors);
+        assert!(client.write_one(Measurement::new("a"), None).wait().is_ok());
+    }
+
+    #[test]
+    fn test_query_admin() {
+        let (client, sent) = recording();
+
+        client.query_admin("SHOW DIAGNOSTICS".to_string()).wait().unwrap();
+        client.query("SHOW MEASUREMENTS".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("SHOW DIAGNOSTICS"), sent[0].query.get("q").map(|s| &s[..]));
+        assert!(!sent[0].query.contains_key("db"));
+        assert_eq!(Some("test"), sent[1].query.get("db").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_write_many_with_lines() {
+        let (mut client, sent) = recording();
+        client.set_max_batch(2).unwrap();
+
+        let mut measurements = vec![];
+        for i in 0..3 {
+            let mut measurement = Measurement::new("key");
+            measurement.add_field("value", Value::Integer(i));
+            measurements.push(measurement);
+        }
+
+        let (result, lines) = client.write_many_with_lines(&measurements, None);
+        result.wait().unwrap();
+
+        let sent: Vec<String> = sent.lock().unwrap().iter().map(|sent| sent.body.clone().unwrap()).collect();
+        assert_eq!(vec!["key value=0i\nkey value=1i".to_string(), "key value=2i".to_string()], lines);
+        assert_eq!(sent, lines);
+    }
+
+    #[test]
+    fn test_retention_policy() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let credentials = Credentials { username: "gobwas", password: "1234", database: "mydb/weekly" };
+        let mut client = HttpClient::new(credentials, Box::new(LineSerializer::new()), Box::new(RecordingHurl { sent: sent.clone() }));
+        client.add_host("http://localhost:8086");
+        client.set_combined_database(true);
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        client.query("SELECT * FROM key".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        for sent in sent.iter() {
+            assert_eq!(Some("mydb"), sent.query.get("db").map(|s| &s[..]));
+            assert_eq!(Some("weekly"), sent.query.get("rp").map(|s| &s[..]));
+        }
+
+        let sent = Arc::new(Mutex::new(vec![]));
+        let credentials = Credentials { username: "gobwas", password: "1234", database: "mydb/rp/extra" };
+        let mut client = HttpClient::new(credentials, Box::new(LineSerializer::new()), Box::new(RecordingHurl { sent: sent.clone() }));
+        client.add_host("http://localhost:8086");
+        client.set_combined_database(true);
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        // overridden database does not need the malformed one
+        let mut options = WriteOptions::default();
+        options.set_database("other");
+        client.write(&[Measurement::new("key")], &options).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(Some("other"), sent[0].query.get("db").map(|s| &s[..]));
+        assert_eq!(None, sent[0].query.get("rp"));
+    }
+
+    #[test]
+    fn test_database_sent_as_is() {
+        for database in &["", "my/db"] {
+            let sent = Arc::new(Mutex::new(vec![]));
+            let mut client = HttpClient::new(Credentials::none(database), Box::new(LineSerializer::new()), Box::new(RecordingHurl { sent: sent.clone() }));
+            client.add_host("http://localhost:8086");
+
+            client.write_one(Measurement::new("key"), None).wait().unwrap();
+            client.query("SHOW DATABASES".to_string(), None).wait().unwrap();
+
+            let sent = sent.lock().unwrap();
+            assert_eq!(2, sent.len());
+            for sent in sent.iter() {
+                assert_eq!(Some(*database), sent.query.get("db").map(|s| &s[..]));
+                assert_eq!(None, sent.query.get("rp"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_write_many_routed() {
+        let (client, sent) = recording();
+
+        let mut measurements = vec![];
+        for (i, shard) in ["a", "b", "a"].iter().enumerate() {
+            let mut measurement = Measurement::new("key");
+            measurement.add_tag("shard", *shard);
+            measurement.add_field("value", Value::Integer(i as i64));
+            measurements.push(measurement);
+        }
+
+        client.write_many_routed(&measurements, None, |measurement| {
+            format!("http://{}:8086", measurement.tags["shard"])
+        }).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(2, sent.len());
+        assert_eq!("http://a:8086/write", sent[0].url);
+        assert_eq!(Some("key,shard=a value=0i\nkey,shard=a value=2i".to_string()), sent[0].body);
+        assert_eq!("http://b:8086/write", sent[1].url);
+        assert_eq!(Some("key,shard=b value=1i".to_string()), sent[1].body);
+    }
+
+    #[test]
+    fn test_strict_precision() {
+        let (mut client, sent) = recording();
+        client.set_strict_precision(true);
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+
+        measurement.set_timestamp_with_precision(1434055562000000000, Precision::Nanoseconds);
+        client.write_one(measurement.clone(), Some(Precision::Milliseconds)).wait().unwrap();
+
+        measurement.set_timestamp_with_precision(1434055562000000001, Precision::Nanoseconds);
+        match client.write_one(measurement.clone(), Some(Precision::Milliseconds)).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("1434055562000000001"), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+        client.write_one(measurement.clone(), None).wait().unwrap();
+
+        client.set_strict_precision(false);
+        client.write_one(measurement, Some(Precision::Milliseconds)).wait().unwrap();
+
+        assert_eq!(3, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_strict_names() {
+        let (mut client, sent) = recording();
+        client.set_strict_names(true);
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("time", Value::Integer(1));
+
+        match client.write_one(measurement.clone(), None).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("\"time\""), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        let mut tagged = Measurement::new("key");
+        tagged.add_field("value", Value::Integer(1));
+        tagged.add_tag("_measurement", "x");
+        assert!(client.write_one(tagged, None).wait().is_err());
+        assert!(sent.lock().unwrap().is_empty());
+
+        client.set_strict_names(false);
+        client.write_one(measurement, None).wait().unwrap();
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_ready() {
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let mut client = {
+            let requests = requests.clone();
+
+            before(Box::new(move || {
+                match requests.fetch_add(1, Ordering::SeqCst) {
+                    0 => Box::new(futures::future::err("connection refused".to_string())),
+                    1 => Box::new(futures::future::ok(Response { status: 503, headers: HashMap::new(), body: String::new() })),
+                    _ => Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: String::new() }))
+                }
+            }))
+        };
+        client.add_host("http://localhost:8086");
+
+        Runtime::new().unwrap().block_on(client.ready(Duration::from_secs(5))).unwrap();
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+
+        let mut client = before(Box::new(|| Box::new(futures::future::err("connection refused".to_string()))));
+        client.add_host("http://localhost:8086");
+
+        match Runtime::new().unwrap().block_on(client.ready(Duration::from_millis(100))) {
+            Err(ClientError::Timeout) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_write_many_with_deadline() {
+        let mut client = before(Box::new(|| Box::new(futures::future::empty())));
+        client.add_host("http://localhost:8086");
+
+        let start = Instant::now();
+        let result = Runtime::new().unwrap().block_on(
+            client.write_many_with_deadline(&[Measurement::new("key")], None, start + Duration::from_millis(50))
+        );
+
+        match result {
+            Err(ClientError::Timeout) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        let mut client = before(Box::new(|| Box::new(futures::future::err("connection refused".to_string()))));
+        client.add_host("http://localhost:8086");
+
+        let result = Runtime::new().unwrap().block_on(
+            client.write_many_with_deadline(&[Measurement::new("key")], None, Instant::now() + Duration::from_secs(1))
+        );
+
+        match result {
+            Err(ClientError::Communication(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_set_max_batch() {
+        let (mut client, sent) = recording();
+
+        match client.set_max_batch(0) {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        client.write_many(&[Measurement::new("a"), Measurement::new("b")], None).wait().unwrap();
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    fn unordered_series() -> Vec<Measurement<'static>> {
+        let mut measurements = vec![];
+        for (i, host) in ["a", "b", "a", "c", "b"].iter().enumerate() {
+            let mut measurement = Measurement::new("cpu");
+            measurement.add_tag("host", *host);
+            measurement.add_field("value", Value::Integer(1));
+            measurement.set_timestamp(i as i64);
+            measurements.push(measurement);
+        }
+        measurements
+    }
+
+    const GROUPED_SERIES: &str = "cpu,host=a value=1i 0\ncpu,host=a value=1i 2\ncpu,host=b value=1i 1\ncpu,host=b value=1i 4\ncpu,host=c value=1i 3";
+
+    #[test]
+    fn test_group_by_series() {
+        let (mut client, sent) = recording();
+        client.set_group_by_series(true);
+
+        client.write_many(&unordered_series(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some(GROUPED_SERIES.to_string()), sent[0].body);
+    }
+
+    #[test]
+    fn test_group_by_series_callback_and_batch() {
+        let (mut client, sent) = recording();
+        client.set_group_by_series(true);
+
+        client.write_with_callback(&unordered_series(), None, |_, _| ChunkControl::Continue).wait().unwrap();
+
+        let batch = client.batch(None, 100);
+        batch.enqueue(&unordered_series()).wait().unwrap();
+        batch.flush().wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(2, sent.len());
+        assert!(sent.iter().all(|request| request.body == Some(GROUPED_SERIES.to_string())));
+    }
+
+    #[test]
+    fn test_credentials_none() {
+        let (client, sent) = recording();
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        assert!(sent.lock().unwrap()[0].authenticated);
+
+        let sent = Arc::new(Mutex::new(vec![]));
+        let mut client = HttpClient::new(Credentials::none("test"), Box::new(LineSerializer::new()), Box::new(RecordingHurl { sent: sent.clone() }));
+        client.add_host("http://localhost:8086");
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        client.query("SHOW DATABASES".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(2, sent.len());
+        assert!(sent.iter().all(|sent| !sent.authenticated));
+    }
+
+    #[test]
+    fn test_prefix() {
+        let (mut client, sent) = recording();
+        client.set_prefix("tenant a,");
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_field("value", Value::Integer(1));
+        measurement.set_timestamp_with_precision(1, Precision::Seconds);
+
+        client.write_one(measurement.clone(), Some(Precision::Milliseconds)).wait().unwrap();
+        client.write_one(Measurement::new("mem"), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("tenant\\ a\\,cpu value=1i 1000".to_string()), sent[0].body);
+        assert_eq!(Some("tenant\\ a\\,mem".to_string()), sent[1].body);
+        assert_eq!("cpu", measurement.key);
+    }
+
+    #[test]
+    fn test_prefix_series_key_len() {
+        let (mut client, sent) = recording();
+        client.set_max_series_key_len(8);
+        client.write_one(Measurement::new("cpu"), None).wait().unwrap();
+
+        client.set_prefix("tenant_");
+
+        match client.write_one(Measurement::new("cpu"), None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_query_raw() {
+        let (mut client, sent) = recording();
+        client.set_header("x-custom", "value");
+
+        let mut params = HashMap::new();
+        params.insert("q", "SHOW STATS".to_string());
+
+        client.query_raw(Method::POST, "/query", params, Some("body".to_string())).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!("http://localhost:8086/query", sent[0].url);
+        assert_eq!(1, sent[0].query.len());
+        assert_eq!(Some("SHOW STATS"), sent[0].query.get("q").map(|s| &s[..]));
+        assert!(sent[0].headers.is_empty());
+        assert!(sent[0].authenticated);
+        assert_eq!(Some("body".to_string()), sent[0].body);
+    }
+
+    #[test]
+    fn test_write_with_options() {
+        let (client, sent) = recording();
+
+        let mut options = WriteOptions::default();
+        options.set_precision(Precision::Seconds)
+            .set_consistency(Consistency::Quorum)
+            .set_database("other")
+            .set_retention_policy("weekly");
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+        measurement.set_timestamp(1434055562);
+
+        client.write(&[measurement], &options).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(1, sent.len());
+        assert_eq!(Some("other"), sent[0].query.get("db").map(|s| &s[..]));
+        assert_eq!(Some("weekly"), sent[0].query.get("rp").map(|s| &s[..]));
+        assert_eq!(Some("s"), sent[0].query.get("precision").map(|s| &s[..]));
+        assert_eq!(Some("quorum"), sent[0].query.get("consistency").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_write_with_default_options() {
+        let (client, sent) = recording();
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+
+        client.write(&[measurement], &WriteOptions::default()).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(Some("test"), sent[0].query.get("db").map(|s| &s[..]));
+        assert!(!sent[0].query.contains_key("rp"));
+        assert!(!sent[0].query.contains_key("precision"));
+        assert!(!sent[0].query.contains_key("consistency"));
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(Ok(0), parse_http_date("Thu, 01 Jan 1970 00:00:00 GMT"));
+        assert_eq!(Ok(784111777), parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"));
+        assert_eq!(Ok(1709210096), parse_http_date("Thu, 29 Feb 2024 12:34:56 GMT"));
+        assert!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST").is_err());
+        assert!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT").is_err());
+    }
+
+    #[test]
+    fn test_clock_skew() {
+        let mut client = before(Box::new(|| {
+            let mut headers = HashMap::new();
+            headers.insert("date".to_string(), "Sun, 06 Nov 1994 08:49:37 GMT".to_string());
+
+            Box::new(futures::future::ok(Response { status: 204, headers, body: String::new() }))
+        }));
+        client.add_host("http://localhost:8086");
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let skew = client.clock_skew().wait().unwrap();
+
+        assert!((784111777 - now - skew).abs() <= 1);
+    }
+
+    #[test]
+    fn test_sink() {
+        let (mut client, sent) = recording();
+        client.set_max_batch(2).unwrap();
+
+        let measurements: Vec<Measurement> = (0..5).map(|i| {
+            let mut measurement = Measurement::new("key");
+            measurement.add_field("i", Value::Integer(i));
+            measurement
+        }).collect();
+
+        let (_, _sink) = futures::stream::iter_ok::<_, ClientError>(measurements).forward(client.sink(Some(Precision::Seconds))).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        let bodies: Vec<_> = sent.iter().map(|sent| sent.body.clone().unwrap()).collect();
+
+        assert_eq!(vec!["key i=0i\nkey i=1i", "key i=2i\nkey i=3i", "key i=4i"], bodies);
+        assert_eq!(Some("s"), sent[0].query.get("precision").map(|s| &s[..]));
+    }
+
+    fn non_finite_measurements() -> Vec<Measurement<'static>> {
+        let mut nan = Measurement::new("key");
+        nan.add_field("value", Value::Float(f64::NAN));
+        nan.add_field("count", Value::Integer(1));
+
+        let mut inf = Measurement::new("inf");
+        inf.add_field("value", Value::Float(f64::INFINITY));
+
+        let mut ok = Measurement::new("ok");
+        ok.add_field("value", Value::Float(0.5));
+
+        vec![nan, inf, ok]
+    }
+
+    #[test]
+    fn test_non_finite_policy_error() {
+        let (client, sent) = recording();
+
+        match client.write_many(&non_finite_measurements(), None).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("\"value\""), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert!(sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_non_finite_policy_skip() {
+        let (mut client, sent) = recording();
+        client.set_non_finite_policy(NonFinitePolicy::Skip);
+
+        client.write_many(&non_finite_measurements(), None).wait().unwrap();
+
+        assert_eq!(Some("key count=1i\nok value=0.5".to_string()), sent.lock().unwrap()[0].body);
+    }
+
+    #[test]
+    fn test_non_finite_policy_skip_whole_chunk() {
+        let (mut client, sent) = recording();
+        client.set_non_finite_policy(NonFinitePolicy::Skip);
+        client.set_max_batch(1).unwrap();
+
+        let measurements = non_finite_measurements();
+
+        client.write_many(&measurements[1..], None).wait().unwrap();
+
+        let batch = client.batch(None, 10);
+        batch.enqueue(&measurements[1..]).wait().unwrap();
+        batch.flush().wait().unwrap();
+
+        let (_, _sink) = futures::stream::iter_ok::<_, ClientError>(measurements[1..].to_vec()).forward(client.sink(None)).wait().unwrap();
+
+        let bodies: Vec<_> = sent.lock().unwrap().iter().map(|sent| sent.body.clone().unwrap()).collect();
+        assert_eq!(vec!["ok value=0.5"; 3], bodies);
+    }
+
+    #[test]
+    fn test_non_finite_policy_zero() {
+        let (mut client, sent) = recording();
+        client.set_non_finite_policy(NonFinitePolicy::Zero);
+
+        client.write_many(&non_finite_measurements(), None).wait().unwrap();
+
+        assert_eq!(Some("key count=1i,value=0\ninf value=0\nok value=0.5".to_string()), sent.lock().unwrap()[0].body);
+    }
+
+    #[test]
+    fn test_query_time_range() {
+        let (client, sent) = recording();
+
+        let results = client.query_time_range("SELECT * FROM cpu WHERE $timeFilter", 0, 30, 10, None).wait().unwrap();
+        assert_eq!(3, results.len());
+
+        let queries: Vec<String> = sent.lock().unwrap().iter().map(|sent| sent.query["q"].clone()).collect();
+        assert_eq!(vec![
+            "SELECT * FROM cpu WHERE time >= 0 AND time < 10",
+            "SELECT * FROM cpu WHERE time >= 10 AND time < 20",
+            "SELECT * FROM cpu WHERE time >= 20 AND time < 30"
+        ], queries);
+
+        match client.query_time_range("SELECT * FROM cpu", 0, 30, 10, None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_query_time_range_half_open() {
+        let (mut client, sent) = recording();
+        client.set_circuit_breaker(CircuitBreaker::new(1, Duration::from_millis(20)));
+        client.breaker.as_ref().unwrap().record(false);
+
+        thread::sleep(Duration::from_millis(30));
+
+        // the first window probes the circuit, the next ones are sent once it is closed
+        let results = client.query_time_range("SELECT * FROM cpu WHERE $timeFilter", 0, 30, 10, None).wait().unwrap();
+        assert_eq!(3, results.len());
+        assert_eq!(3, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_stats() {
+        let (client, _) = failing_second();
+        assert_eq!(0, client.stats().requests);
+
+        for _ in 0..3 {
+            let _ = client.write_one(Measurement::new("key"), None).wait();
+        }
+
+        let stats = client.stats();
+        assert_eq!(3, stats.requests);
+        assert_eq!(1, stats.errors);
+    }
+
+    #[test]
+    fn test_write_with_precision_str() {
+        let (client, sent) = recording();
+
+        let mut options = WriteOptions::default();
+        options.set_precision_str("ms").unwrap();
+        assert!(options.set_precision_str("msec").is_err());
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+
+        client.write(&[measurement], &options).wait().unwrap();
+
+        assert_eq!(Some("ms"), sent.lock().unwrap()[0].query.get("precision").map(|s| &s[..]));
+    }
+
+    #[test]
+    fn test_hash_tag() {
+        let (mut client, sent) = recording();
+        client.set_hash_tag("hash");
+
+        let mut a = Measurement::new("key");
+        a.add_field("value", Value::Integer(1));
+        let mut b = Measurement::new("key");
+        b.add_field("value", Value::Integer(2));
+
+        client.write_many(&[a.clone(), a.clone(), b], None).wait().unwrap();
+
+        let body = sent.lock().unwrap()[0].body.clone().unwrap();
+        let lines: Vec<&str> = body.lines().collect();
+
+        assert_eq!(format!("key,hash={:016x} value=1i", a.content_hash()), lines[0]);
+        assert_eq!(lines[0], lines[1]);
+        assert_ne!(lines[0].split(' ').next(), lines[2].split(' ').next());
+    }
+
+    #[test]
+    fn test_hash_tag_validation() {
+        let (mut client, sent) = recording();
+        client.set_hash_tag("hash");
+        client.set_reject_duplicate_points(true);
+
+        let mut a = Measurement::new("key");
+        a.add_field("value", Value::Integer(1));
+        a.set_timestamp(1);
+        let mut b = a.clone();
+        b.add_field("value", Value::Integer(2));
+
+        // distinct points get distinct series
+        client.write_many(&[a.clone(), b], None).wait().unwrap();
+
+        match client.write_many(&[a.clone(), a.clone()], None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        client.set_max_tags(0);
+        match client.write_one(a.clone(), None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        client.set_max_tags(1);
+        client.set_max_series_key_len(20);
+        match client.write_one(a, None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_request_ids() {
+        let (mut client, sent) = recording();
+        client.set_request_ids(true);
+
+        client.write_one(Measurement::new("key"), None).wait().unwrap();
+        client.query("SHOW DATABASES".to_string(), None).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        let ids: Vec<&String> = sent.iter().map(|sent| &sent.headers["x-request-id"]).collect();
+
+        assert_eq!(36, ids[0].len());
+        assert_eq!(Some('4'), ids[0].chars().nth(14));
+        assert_ne!(ids[0], ids[1]);
+    }
+
+    #[test]
+    fn test_request_ids_in_errors() {
+        let mut client = before(Box::new(|| {
+            let mut headers = HashMap::new();
+            headers.insert("x-request-id".to_string(), "echoed".to_string());
+
+            Box::new(futures::future::ok(Response { status: 400, headers, body: "bad".to_string() }))
+        }));
+        client.add_host("http://localhost:8086");
+        client.set_request_ids(true);
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Syntax(message)) => assert_eq!("bad (request id: echoed)", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        let mut client = before(Box::new(|| Box::new(futures::future::err("refused".to_string()))));
+        client.add_host("http://localhost:8086");
+        client.set_request_ids(true);
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Communication(message)) => assert!(message.starts_with("refused (request id: "), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_write_fanout() {
+        let (mut client, requests) = failing_second();
+        client.set_max_batch(10).unwrap();
+
+        let mut primary = WriteOptions::default();
+        primary.set_database("primary");
+        let mut replica = WriteOptions::default();
+        replica.set_database("replica");
+
+        let outcomes = client.write_fanout(&[Measurement::new("a"), Measurement::new("b")], &[primary, replica]).wait().unwrap();
+
+        assert_eq!(2, requests.load(Ordering::SeqCst));
+        assert_eq!(2, outcomes.len());
+        assert!(outcomes[0].is_ok());
+        match outcomes[1] {
+            Err(ClientError::Syntax(_)) => {},
+            ref res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_query_pages() {
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let mut client = {
+            let requests = requests.clone();
+
+            before(Box::new(move || {
+                let body = match requests.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => r#"{"results":[{"statement_id":0,"series":[{"name":"cpu","columns":["time"],"values":[[1]]}]}]}"#,
+                    _ => r#"{"results":[{"statement_id":0}]}"#
+                };
+
+                Box::new(futures::future::ok(Response { status: 200, headers: HashMap::new(), body: body.to_string() }))
+            }))
+        };
+        client.add_host("http://localhost:8086");
+
+        let pages = client.query_pages("SELECT * FROM cpu", 1, None).collect().wait().unwrap();
+
+        assert_eq!(2, pages.len());
+        assert_eq!(3, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_statement_error() {
+        assert_eq!(
+            Some("error parsing query: found <, expected \"identifier\" \u{1F600}".to_string()),
+            statement_error(r#"{"results":[{"statement_id":0,"error":"error parsing query: found \u003c, expected \"identifier\" \ud83d\ude00"}]}"#)
+        );
+        assert_eq!(
+            Some("database not found: db".to_string()),
+            statement_error(r#"{"results":[{"statement_id":0},{"statement_id":1,"error":"database not found: db"}]}"#)
+        );
+        assert_eq!(None, statement_error(r#"{"results":[{"statement_id":0,"series":[{"name":"m","tags":{"error":"x"},"columns":["time"],"values":[[1]]}]}]}"#));
+    }
+
+    #[test]
+    fn test_query_pages_statement_error() {
+        let requests = Arc::new(AtomicUsize::new(0));
+
+        let mut client = {
+            let requests = requests.clone();
+
+            before(Box::new(move || {
+                let body = match requests.fetch_add(1, Ordering::SeqCst) {
+                    0 => r#"{"results":[{"statement_id":0,"series":[{"name":"cpu","columns":["time"],"values":[[1]]}]}]}"#,
+                    _ => r#"{"results":[{"statement_id":0,"error":"timeout"}]}"#
+                };
+
+                Box::new(futures::future::ok(Response { status: 200, headers: HashMap::new(), body: body.to_string() }))
+            }))
+        };
+        client.add_host("http://localhost:8086");
+
+        match client.query_pages("SELECT * FROM cpu", 1, None).collect().wait() {
+            Err(ClientError::CouldNotComplete(error)) => assert_eq!("timeout", error),
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert_eq!(2, requests.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_write_one_now() {
+        let (client, sent) = recording();
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        client.write_one_now(measurement.clone(), Some(Precision::Seconds)).wait().unwrap();
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+
+        measurement.set_timestamp_with_precision(1434055562, Precision::Seconds);
+        client.write_one_now(measurement, Some(Precision::Seconds)).wait().unwrap();
+
+        let sent = sent.lock().unwrap();
+        let stamped: i64 = sent[0].body.as_ref().unwrap().rsplit(' ').next().unwrap().parse().unwrap();
+
+        assert!(before <= stamped && stamped <= after);
+        assert_eq!(Some("key value=1i 1434055562".to_string()), sent[1].body);
+    }
+
+    fn responding(body: &'static str) -> HttpClient<'static> {
+        let mut client = before(Box::new(move || Box::new(futures::future::ok(Response { status: 200, headers: HashMap::new(), body: body.to_string() }))));
+        client.add_host("http://localhost:8086");
+        client
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_list_continuous_queries() {
+        let client = responding(r#"{"results":[{"statement_id":0,"series":[{"name":"telegraf","columns":["name","query"],"values":[["cq","CREATE CONTINUOUS QUERY cq ON telegraf BEGIN SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h) END"]]}]}]}"#);
+
+        let cqs = client.list_continuous_queries().wait().unwrap();
+        assert_eq!(1, cqs.len());
+        assert_eq!("telegraf", cqs[0].database);
+        assert_eq!("cq", cqs[0].name);
+
+        let client = responding(r#"{"results":[{"statement_id":0,"error":"not authorized"}]}"#);
+        match client.list_continuous_queries().wait() {
+            Err(ClientError::CouldNotComplete(error)) => assert_eq!("not authorized", error),
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        let client = responding(r#"{"results":[{"statement_id":0}]}"#);
+        assert!(client.execute("CREATE DATABASE db".to_string()).wait().is_ok());
+
+        let client = responding(r#"{"results":[{"statement_id":0,"error":"retention policy not found: \"weekly\""}]}"#);
+        match client.execute("DROP RETENTION POLICY weekly ON db".to_string()).wait() {
+            Err(ClientError::CouldNotComplete(message)) => assert_eq!("retention policy not found: \"weekly\"", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+
+    #[test]
+    fn test_reject_duplicate_points() {
+        let (mut client, sent) = recording();
+        client.set_reject_duplicate_points(true);
+
+        let mut a = Measurement::new("cpu");
+        a.add_tag("a", "1");
+        a.add_tag("b", "2");
+        a.add_field("user", Value::Float(0.5));
+        a.set_timestamp_with_precision(1434055562, Precision::Seconds);
+
+        let mut b = Measurement::new("cpu");
+        b.add_tag("b", "2");
+        b.add_tag("a", "1");
+        b.add_field("system", Value::Float(0.5));
+        b.set_timestamp_with_precision(1434055562000, Precision::Milliseconds);
+
+        match client.write_many(&[a.clone(), b.clone()], None).wait() {
+            Err(ClientError::Validation(message)) => assert!(message.contains("cpu,a=1,b=2"), "{}", message),
+            res => panic!("unexpected result: {:?}", res)
+        }
+
+        b.set_timestamp_with_precision(1434055563000, Precision::Milliseconds);
+        client.write_many(&[a, b], None).wait().unwrap();
+
+        assert_eq!(1, sent.lock().unwrap().len());
+    }
+
+    struct CapturingLogger(Mutex<Vec<String>>);
+
+    impl ::log::Log for CapturingLogger {
+        fn enabled(&self, _: &::log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &::log::Record) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(vec![]));
+
+    #[test]
+    fn test_body_size_warning() {
+        let _ = ::log::set_logger(&LOGGER);
+        ::log::set_max_level(::log::LevelFilter::Warn);
+
+        let (mut client, _) = recording();
+        client.set_body_size_warning(40);
+
+        let measurements: Vec<Measurement> = (0..3).map(|i| {
+            let mut measurement = Measurement::new("body_size_warning");
+            measurement.add_field("i", Value::Integer(i));
+            measurement
+        }).collect();
+
+        // no other test sets this threshold
+        let warnings = || LOGGER.0.lock().unwrap().iter().filter(|message| message.ends_with("exceeds 40 bytes")).count();
+
+        client.write_many(&measurements[..1], None).wait().unwrap();
+        assert_eq!(0, warnings());
+
+        client.write_many(&measurements, None).wait().unwrap();
+        assert_eq!(1, warnings());
+
+        client.set_body_size_warning(20);
+        client.write_many(&measurements[..1], None).wait().unwrap();
+        assert!(LOGGER.0.lock().unwrap().iter().any(|message| message.contains("with 1 point exceeds 20 bytes")));
+    }
+
+    #[test]
+    fn test_query_chunked() {
+        let (client, sent) = recording();
+
+        client.query_chunked("SELECT * FROM cpu".to_string(), Some(Precision::Seconds), 1000).wait().unwrap();
+
+        {
+            let sent = sent.lock().unwrap();
+            assert_eq!(Some("true"), sent[0].query.get("chunked").map(|s| &s[..]));
+            assert_eq!(Some("1000"), sent[0].query.get("chunk_size").map(|s| &s[..]));
+            assert_eq!(Some("s"), sent[0].query.get("epoch").map(|s| &s[..]));
+        }
+
+        match client.query_chunked("SELECT * FROM cpu".to_string(), None, 0).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert_eq!(1, sent.lock().unwrap().len());
+
+        let client = responding("{\"results\":[{\"statement_id\":0,\"partial\":true}]}\n{\"results\":[{\"statement_id\":0}]}\n");
+        assert_eq!(2, client.query_chunked("SELECT * FROM cpu".to_string(), None, 1).wait().unwrap().len());
+    }
+
+    fn record(batch: &super::WriteBatch, key: &'static str) {
+        let mut measurement = Measurement::new(key);
+        measurement.add_field("value", Value::Integer(1));
+
+        batch.enqueue(&[measurement]).wait().unwrap();
+    }
+
+    #[test]
+    fn test_batch() {
+        let (client, sent) = recording();
+        let batch = client.batch(Some(Precision::Seconds), 3);
+
+        record(&batch, "a");
+        record(&batch, "b");
+        assert_eq!(2, batch.len());
+        assert!(sent.lock().unwrap().is_empty());
+
+        batch.flush().wait().unwrap();
+        assert!(batch.is_empty());
+
+        record(&batch, "c");
+        record(&batch, "d");
+        record(&batch, "e");
+
+        let sent = sent.lock().unwrap();
+        assert_eq!(2, sent.len());
+        assert_eq!(Some("a value=1i\nb value=1i".to_string()), sent[0].body);
+        assert_eq!(Some("c value=1i\nd value=1i\ne value=1i".to_string()), sent[1].body);
+        assert_eq!(Some("s"), sent[0].query.get("precision").map(|s| &s[..]));
+    }
+}
@@ -1,13 +1,26 @@
 use ::measurement::Measurement;
 use std::io;
+use std::fmt;
+use std::str::FromStr;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
 use futures::Future;
 
+pub mod breaker;
 pub mod http;
+pub mod readonly;
+pub mod tcp;
+pub mod wal;
 
+/// InfluxDB client.
+///
+/// Methods return futures, which must be polled for requests to complete. Chunks of a write are
+/// sent concurrently, so dropping a future aborts the requests still in flight, while chunks
+/// already written stay written.
 pub trait Client {
-    fn write_many(&self, &[Measurement], Option<Precision>) -> ClientWriteResult;
-    fn write_one(&self, Measurement, Option<Precision>) -> ClientWriteResult;
-    fn query(&self, String, Option<Precision>) -> ClientReadResult;
+    fn write_many(&self, measurements: &[Measurement], precision: Option<Precision>) -> ClientWriteResult;
+    fn write_one(&self, measurement: Measurement, precision: Option<Precision>) -> ClientWriteResult;
+    fn query(&self, q: String, epoch: Option<Precision>) -> ClientReadResult;
 }
 
 pub struct Credentials<'a> {
@@ -16,6 +29,61 @@ pub struct Credentials<'a> {
     pub database: &'a str
 }
 
+impl<'a> Credentials<'a> {
+    /// Constructs `Credentials` for InfluxDB without authentication, so requests are sent without
+    /// the `Authorization` header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::Credentials;
+    ///
+    /// let credentials = Credentials::none("mydb");
+    ///
+    /// assert!(!credentials.is_authenticated());
+    /// ```
+    pub fn none(database: &'a str) -> Credentials<'a> {
+        Credentials {
+            username: "",
+            password: "",
+            database
+        }
+    }
+
+    /// Returns `false` if both username and password are empty.
+    pub fn is_authenticated(&self) -> bool {
+        !self.username.is_empty() || !self.password.is_empty()
+    }
+
+    /// Splits `database` given as `database/retention_policy`, like `mydb/weekly`, into the
+    /// database name and the optional retention policy.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::Credentials;
+    ///
+    /// let credentials = Credentials {
+    ///     username: "gobwas",
+    ///     password: "xxx",
+    ///     database: "mydb/weekly"
+    /// };
+    ///
+    /// assert_eq!(Ok(("mydb", Some("weekly"))), credentials.database_and_retention_policy());
+    /// ```
+    pub fn database_and_retention_policy(&self) -> Result<(&'a str, Option<&'a str>), String> {
+        let mut parts = self.database.split('/');
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(database), None, None) if !database.is_empty() => Ok((database, None)),
+            (Some(database), Some(rp), None) if !database.is_empty() && !rp.is_empty() => Ok((database, Some(rp))),
+            _ => Err(format!("invalid database \"{}\", expected \"database\" or \"database/retention_policy\"", self.database))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Precision {
     Nanoseconds,
     Microseconds,
@@ -25,8 +93,47 @@ pub enum Precision {
     Hours
 }
 
-impl ToString for Precision {
-    fn to_string(&self) -> String {
+impl Precision {
+    /// Returns the number of nanoseconds in one unit of this precision.
+    pub fn nanoseconds(&self) -> i64 {
+        match *self {
+            Precision::Nanoseconds  => 1,
+            Precision::Microseconds => 1_000,
+            Precision::Milliseconds => 1_000_000,
+            Precision::Seconds      => 1_000_000_000,
+            Precision::Minutes      => 60_000_000_000,
+            Precision::Hours        => 3_600_000_000_000
+        }
+    }
+
+    /// Converts timestamp from this precision to another one.
+    ///
+    /// Converting to a coarser precision rounds down, converting to a finer one saturates on overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::Precision;
+    ///
+    /// assert_eq!(1434055562, Precision::Milliseconds.convert(1434055562999, Precision::Seconds));
+    /// assert_eq!(1434055562000000000, Precision::Seconds.convert(1434055562, Precision::Nanoseconds));
+    /// ```
+    pub fn convert(&self, timestamp: i64, to: Precision) -> i64 {
+        let nanoseconds = i128::from(timestamp) * i128::from(self.nanoseconds());
+        let converted = nanoseconds.div_euclid(i128::from(to.nanoseconds()));
+
+        if converted > i128::from(i64::MAX) {
+            i64::MAX
+        } else if converted < i128::from(i64::MIN) {
+            i64::MIN
+        } else {
+            converted as i64
+        }
+    }
+}
+
+impl fmt::Display for Precision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let s = match *self {
             Precision::Nanoseconds  => "n",
             Precision::Microseconds => "u",
@@ -36,26 +143,82 @@ impl ToString for Precision {
             Precision::Hours        => "h"
         };
 
-        s.to_string()
+        f.write_str(s)
     }
 }
 
-pub type ClientWriteResult = Box<Future<Item=(), Error=ClientError> + Send>;
+impl FromStr for Precision {
+    type Err = String;
+
+    /// Parses precision from the token used in InfluxDB query params, e.g. `ms`.
+    fn from_str(s: &str) -> Result<Precision, String> {
+        match s {
+            "n"  => Ok(Precision::Nanoseconds),
+            "u"  => Ok(Precision::Microseconds),
+            "ms" => Ok(Precision::Milliseconds),
+            "s"  => Ok(Precision::Seconds),
+            "m"  => Ok(Precision::Minutes),
+            "h"  => Ok(Precision::Hours),
+            _    => Err(format!("unknown precision: \"{}\"", s))
+        }
+    }
+}
+
+pub type ClientWriteResult = Box<dyn Future<Item=(), Error=ClientError> + Send>;
 
 // TODO: here parsing json?
-pub type ClientReadResult = Box<Future<Item=String, Error=ClientError> + Send>;
+pub type ClientReadResult = Box<dyn Future<Item=String, Error=ClientError> + Send>;
 
 #[derive(Debug)]
 pub enum ClientError {
     CouldNotComplete(String),
     Communication(String),
+    /// Local I/O error, e.g. of the write-ahead log.
+    Io(io::Error),
     Syntax(String),
     Unexpected(String),
+    Validation(String),
+    CircuitOpen,
+    Timeout,
+    /// Outcomes of all chunks of a write in `ErrorMode::CollectErrors`, when some of them failed.
+    Chunks(Vec<Result<(), ClientError>>),
     Unknown
 }
 
 impl From<io::Error> for ClientError {
     fn from(e: io::Error) -> Self {
-        ClientError::Communication(format!("{}", e))
+        ClientError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientError, Credentials};
+    use std::io;
+
+    #[test]
+    fn test_from_io_error() {
+        match ClientError::from(io::Error::from(io::ErrorKind::ConnectionRefused)) {
+            ClientError::Io(e) => assert_eq!(io::ErrorKind::ConnectionRefused, e.kind()),
+            e => panic!("unexpected error: {:?}", e)
+        }
+    }
+
+    fn credentials(database: &str) -> Credentials<'_> {
+        Credentials {
+            username: "gobwas",
+            password: "1234",
+            database
+        }
+    }
+
+    #[test]
+    fn test_database_and_retention_policy() {
+        assert_eq!(Ok(("mydb", None)), credentials("mydb").database_and_retention_policy());
+        assert_eq!(Ok(("mydb", Some("weekly"))), credentials("mydb/weekly").database_and_retention_policy());
+        assert!(credentials("mydb/rp/extra").database_and_retention_policy().is_err());
+        assert!(credentials("mydb/").database_and_retention_policy().is_err());
+        assert!(credentials("/weekly").database_and_retention_policy().is_err());
+        assert!(credentials("").database_and_retention_policy().is_err());
     }
 }
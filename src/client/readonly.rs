@@ -0,0 +1,122 @@
+use ::measurement::Measurement;
+use ::client::{Client, ClientError, ClientReadResult, ClientWriteResult, Precision};
+use ::client::http::HttpClient;
+use futures::{future, Future, Stream};
+use std::time::Duration;
+
+/// Client for read-only credentials, exposing only queries.
+///
+/// It implements `Client` to be usable where one is expected, but its writes fail with
+/// `ClientError::Validation` right away, instead of wasting a round trip to get `403 Forbidden`.
+///
+/// # Examples
+///
+/// ```
+/// use influent::create_client;
+/// use influent::client::Credentials;
+/// use influent::client::readonly::ReadOnlyClient;
+///
+/// let credentials = Credentials {
+///     username: "reader",
+///     password: "xxx",
+///     database: "mydb"
+/// };
+///
+/// let client = ReadOnlyClient::new(create_client(credentials, vec!["http://localhost:8086"]));
+/// ```
+pub struct ReadOnlyClient<'a> {
+    client: HttpClient<'a>
+}
+
+impl<'a> ReadOnlyClient<'a> {
+    /// Constructs new `ReadOnlyClient` querying through given client.
+    pub fn new(client: HttpClient<'a>) -> ReadOnlyClient<'a> {
+        ReadOnlyClient { client }
+    }
+
+    /// Runs several statements in one request, see `HttpClient::query_multi`.
+    pub fn query_multi(&self, statements: &[String], epoch: Option<Precision>) -> ClientReadResult {
+        self.client.query_multi(statements, epoch)
+    }
+
+    /// Runs query with chunked results, see `HttpClient::query_chunked`.
+    pub fn query_chunked(&self, q: String, epoch: Option<Precision>, chunk_size: u32) -> Box<dyn Future<Item=Vec<String>, Error=ClientError> + Send> {
+        self.client.query_chunked(q, epoch, chunk_size)
+    }
+
+    /// Runs query page by page, see `HttpClient::query_pages`.
+    pub fn query_pages(&self, q: &str, page_size: u64, epoch: Option<Precision>) -> Box<dyn Stream<Item=String, Error=ClientError> + Send + '_> {
+        self.client.query_pages(q, page_size, epoch)
+    }
+
+    /// Runs query over time range in windows, see `HttpClient::query_time_range`.
+    pub fn query_time_range(&self, q: &str, start: i64, end: i64, window: i64, epoch: Option<Precision>) -> Box<dyn Future<Item=Vec<String>, Error=ClientError> + Send + '_> {
+        self.client.query_time_range(q, start, end, window, epoch)
+    }
+
+    /// Waits until the server is ready, see `HttpClient::ready`.
+    pub fn ready(&self, timeout: Duration) -> Box<dyn Future<Item=(), Error=ClientError> + Send + '_> {
+        self.client.ready(timeout)
+    }
+}
+
+impl<'a> Client for ReadOnlyClient<'a> {
+    fn query(&self, q: String, epoch: Option<Precision>) -> ClientReadResult {
+        self.client.query(q, epoch)
+    }
+
+    fn write_one(&self, _measurement: Measurement, _precision: Option<Precision>) -> ClientWriteResult {
+        Box::new(future::err(read_only()))
+    }
+
+    fn write_many(&self, _measurements: &[Measurement], _precision: Option<Precision>) -> ClientWriteResult {
+        Box::new(future::err(read_only()))
+    }
+}
+
+fn read_only() -> ClientError {
+    ClientError::Validation("client is read-only".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReadOnlyClient;
+    use ::client::{Client, ClientError, Credentials};
+    use ::client::http::HttpClient;
+    use ::hurl::{Hurl, HurlResult, Request, Response};
+    use ::measurement::Measurement;
+    use ::serializer::line::LineSerializer;
+    use futures::{self, Future};
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    struct RecordingHurl {
+        urls: Arc<Mutex<Vec<String>>>
+    }
+
+    impl Hurl for RecordingHurl {
+        fn request(&self, req: Request) -> HurlResult {
+            self.urls.lock().unwrap().push(req.url.to_string());
+            Box::new(futures::future::ok(Response { status: 200, headers: HashMap::new(), body: String::new() }))
+        }
+    }
+
+    #[test]
+    fn test_read_only() {
+        let urls = Arc::new(Mutex::new(vec![]));
+        let mut client = HttpClient::new(Credentials::none("test"), Box::new(LineSerializer::new()), Box::new(RecordingHurl { urls: urls.clone() }));
+        client.add_host("http://localhost:8086");
+
+        let client = ReadOnlyClient::new(client);
+
+        match client.write_one(Measurement::new("key"), None).wait() {
+            Err(ClientError::Validation(_)) => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+        assert!(client.write_many(&[Measurement::new("key")], None).wait().is_err());
+        assert!(urls.lock().unwrap().is_empty());
+
+        client.query("SHOW DATABASES".to_string(), None).wait().unwrap();
+        assert_eq!(vec!["http://localhost:8086/query".to_string()], *urls.lock().unwrap());
+    }
+}
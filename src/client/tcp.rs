@@ -0,0 +1,193 @@
+use ::measurement::Measurement;
+use ::serializer::Serializer;
+use ::client::{ClientError, Precision};
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Writer streaming line protocol over TCP, e.g. to Telegraf's `socket_listener`.
+///
+/// Unlike HTTP there is no response, so a write succeeds as soon as the lines are handed to the
+/// connection. The connection is opened lazily and kept open; when a write fails, the writer
+/// reconnects and tries once more. Timestamps are sent in nanoseconds, which is what the listener
+/// expects.
+///
+/// Writes are blocking: connecting and writing take up to the timeout each, and concurrent writes
+/// wait for each other, so they should not be called from tasks of an event loop, but from a
+/// dedicated thread.
+pub struct TcpWriter {
+    addr: String,
+    serializer: Box<dyn Serializer + Send + Sync>,
+    timeout: Duration,
+    stream: Mutex<Option<TcpStream>>
+}
+
+impl TcpWriter {
+    /// Constructs new `TcpWriter` connecting to given `host:port`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::tcp::TcpWriter;
+    /// use influent::serializer::line::LineSerializer;
+    ///
+    /// let writer = TcpWriter::new("localhost:8094", Box::new(LineSerializer::new()));
+    /// ```
+    pub fn new(addr: &str, serializer: Box<dyn Serializer + Send + Sync>) -> TcpWriter {
+        TcpWriter {
+            addr: addr.to_string(),
+            serializer,
+            timeout: Duration::from_secs(10),
+            stream: Mutex::new(None)
+        }
+    }
+
+    /// Sets the timeout of connecting and of every write. Defaults to 10 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut error = io::Error::new(io::ErrorKind::InvalidInput, format!("could not resolve \"{}\"", self.addr));
+
+        for addr in self.addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(connection) => {
+                    connection.set_write_timeout(Some(self.timeout))?;
+                    return Ok(connection);
+                },
+                Err(e) => error = e
+            }
+        }
+
+        Err(error)
+    }
+
+    /// Writes measurements, blocking until they are handed to the connection.
+    pub fn write_many(&self, measurements: &[Measurement]) -> Result<(), ClientError> {
+        let mut lines = String::new();
+
+        for measurement in measurements {
+            match (measurement.timestamp, measurement.precision) {
+                (Some(timestamp), Some(p)) if p != Precision::Nanoseconds => {
+                    let mut normalized = measurement.clone();
+                    normalized.set_timestamp_with_precision(p.convert(timestamp, Precision::Nanoseconds), Precision::Nanoseconds);
+                    lines.push_str(&self.serializer.serialize(&normalized));
+                },
+                _ => lines.push_str(&self.serializer.serialize(measurement))
+            }
+            lines.push('\n');
+        }
+
+        let mut stream = self.stream.lock().unwrap();
+
+        let retry = match *stream {
+            Some(ref mut connection) => match connection.write_all(lines.as_bytes()).and_then(|_| connection.flush()) {
+                Ok(()) => return Ok(()),
+                // part of the lines could be sent, so the retry starts a new line, and the listener
+                // drops the truncated one instead of gluing it to the first resent line
+                Err(_) => format!("\n{}", lines)
+            },
+            None => lines
+        };
+
+        // not connected yet, or the connection is broken
+        *stream = None;
+        let mut connection = self.connect()?;
+        connection.write_all(retry.as_bytes())?;
+        connection.flush()?;
+        *stream = Some(connection);
+
+        Ok(())
+    }
+
+    /// Writes one measurement.
+    pub fn write_one(&self, measurement: Measurement) -> Result<(), ClientError> {
+        self.write_many(&[measurement])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TcpWriter;
+    use ::client::{ClientError, Precision};
+    use ::measurement::{Measurement, Value};
+    use ::serializer::line::LineSerializer;
+    use std::io::{self, Read};
+    use std::net::{Shutdown, TcpListener};
+    use std::thread;
+
+    #[test]
+    fn test_write() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut received = String::new();
+            socket.read_to_string(&mut received).unwrap();
+            received
+        });
+
+        let writer = TcpWriter::new(&addr, Box::new(LineSerializer::new()));
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_field("value", Value::Integer(1));
+        measurement.set_timestamp_with_precision(1, Precision::Seconds);
+        writer.write_one(measurement.clone()).unwrap();
+
+        measurement.set_timestamp(2);
+        writer.write_many(&[measurement]).unwrap();
+
+        // closes the connection
+        drop(writer);
+
+        assert_eq!("cpu value=1i 1000000000\ncpu value=1i 2\n", server.join().unwrap());
+    }
+
+    #[test]
+    fn test_reconnect_starts_new_line() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let server = thread::spawn(move || {
+            let mut received = vec![];
+
+            for _ in 0..2 {
+                let (mut socket, _) = listener.accept().unwrap();
+                let mut connection = String::new();
+                socket.read_to_string(&mut connection).unwrap();
+                received.push(connection);
+            }
+
+            received
+        });
+
+        let writer = TcpWriter::new(&addr, Box::new(LineSerializer::new()));
+        writer.write_one(Measurement::new("a")).unwrap();
+
+        // breaks the connection, so the next write fails and reconnects
+        writer.stream.lock().unwrap().as_ref().unwrap().shutdown(Shutdown::Write).unwrap();
+        writer.write_one(Measurement::new("b")).unwrap();
+
+        drop(writer);
+
+        assert_eq!(vec!["a\n", "\nb\n"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        let addr = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.local_addr().unwrap().to_string()
+        };
+
+        let writer = TcpWriter::new(&addr, Box::new(LineSerializer::new()));
+
+        match writer.write_one(Measurement::new("cpu")) {
+            Err(ClientError::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionRefused => {},
+            res => panic!("unexpected result: {:?}", res)
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use ::client::Precision;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const HEADER: &str = "#precision";
+
+/// Disk-backed buffer of line protocol batches which could not be delivered.
+///
+/// Each batch is stored together with its write precision, so batches written with different
+/// precisions could be replayed correctly. The file never grows beyond `max_size` bytes: batches
+/// which do not fit are rejected.
+pub struct WriteAheadLog {
+    path: PathBuf,
+    max_size: u64,
+    lock: Mutex<()>
+}
+
+/// Batch of line protocol read back from the `WriteAheadLog`.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub precision: Option<Precision>,
+    pub lines: String
+}
+
+impl WriteAheadLog {
+    /// Constructs new `WriteAheadLog` stored at given path and limited to `max_size` bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::wal::WriteAheadLog;
+    ///
+    /// let wal = WriteAheadLog::new("/tmp/influent.wal", 64 * 1024 * 1024);
+    /// ```
+    pub fn new<P: Into<PathBuf>>(path: P, max_size: u64) -> WriteAheadLog {
+        WriteAheadLog {
+            path: path.into(),
+            max_size,
+            lock: Mutex::new(())
+        }
+    }
+
+    /// Appends batch of lines to the log.
+    ///
+    /// Returns error if the batch does not fit into the size limit.
+    pub fn append(&self, precision: Option<Precision>, lines: &str) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+
+        let header = match precision {
+            Some(precision) => format!("{} {}\n", HEADER, precision),
+            None => format!("{}\n", HEADER)
+        };
+
+        let size = match fs::metadata(&self.path) {
+            Ok(meta) => meta.len(),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e)
+        };
+
+        if size + (header.len() + lines.len() + 1) as u64 > self.max_size {
+            return Err(io::Error::other(format!("write-ahead log size limit of {} bytes exceeded", self.max_size)));
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(header.as_bytes())?;
+        file.write_all(lines.as_bytes())?;
+        file.write_all(b"\n")
+    }
+
+    /// Reads all batches stored in the log.
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        let _lock = self.lock.lock().unwrap();
+
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e)
+        };
+
+        let mut entries: Vec<Entry> = vec![];
+
+        for line in content.lines() {
+            if let Some(header) = line.strip_prefix(HEADER) {
+                let precision = match header.trim() {
+                    "" => None,
+                    p => Some(p.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?)
+                };
+
+                entries.push(Entry { precision, lines: String::new() });
+                continue;
+            }
+
+            match entries.last_mut() {
+                Some(entry) => {
+                    if !entry.lines.is_empty() {
+                        entry.lines.push('\n');
+                    }
+                    entry.lines.push_str(line);
+                },
+                None => return Err(io::Error::new(io::ErrorKind::InvalidData, "write-ahead log entry has no header"))
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes all batches from the log.
+    pub fn clear(&self) -> io::Result<()> {
+        let _lock = self.lock.lock().unwrap();
+
+        match fs::remove_file(&self.path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{WriteAheadLog, Entry};
+    use ::client::Precision;
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_append_and_read() {
+        let path = env::temp_dir().join(format!("influent-wal-{}.lp", process::id()));
+        let wal = WriteAheadLog::new(path, 1024);
+        wal.clear().unwrap();
+
+        wal.append(Some(Precision::Seconds), "a value=1i 1\nb value=2i 2").unwrap();
+        wal.append(None, "c value=3i 3").unwrap();
+
+        assert_eq!(vec![
+            Entry { precision: Some(Precision::Seconds), lines: "a value=1i 1\nb value=2i 2".to_string() },
+            Entry { precision: None, lines: "c value=3i 3".to_string() },
+        ], wal.entries().unwrap());
+
+        wal.clear().unwrap();
+        assert!(wal.entries().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let path = env::temp_dir().join(format!("influent-wal-limit-{}.lp", process::id()));
+        let wal = WriteAheadLog::new(path, 50);
+        wal.clear().unwrap();
+
+        assert!(wal.append(None, "a value=1i").is_ok());
+        assert!(wal.append(None, "b value=1i").is_ok());
+        assert!(wal.append(None, "c value=1i").is_err());
+        assert_eq!(2, wal.entries().unwrap().len());
+
+        wal.clear().unwrap();
+    }
+}
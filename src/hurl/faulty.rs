@@ -0,0 +1,167 @@
+use futures::{future, Future};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::timer::Delay;
+
+use super::{Request, HurlResult};
+
+use super::Hurl;
+
+/// `Hurl` decorator injecting latency and failures into requests of the inner hurl, to test how
+/// the code around the client behaves when InfluxDB is slow or unreliable.
+///
+/// Failed requests are not passed to the inner hurl and resolve with an error, like connection
+/// errors do. Latency is implemented with tokio timer, so it requires a tokio runtime.
+pub struct FaultyHurl<H> {
+    inner: H,
+    latency: Option<Duration>,
+    failure_rate: f64,
+    failures: Mutex<VecDeque<bool>>,
+    seed: Mutex<u64>
+}
+
+impl<H: Hurl> FaultyHurl<H> {
+    /// Constructs new `FaultyHurl` which passes all requests to the inner hurl.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::hurl::faulty::FaultyHurl;
+    /// use influent::hurl::hyper::HyperHurl;
+    ///
+    /// let mut hurl = FaultyHurl::new(HyperHurl::new());
+    ///
+    /// hurl.set_failure_rate(0.5, 42);
+    /// ```
+    pub fn new(inner: H) -> FaultyHurl<H> {
+        FaultyHurl {
+            inner,
+            latency: None,
+            failure_rate: 0.0,
+            failures: Mutex::new(VecDeque::new()),
+            seed: Mutex::new(0)
+        }
+    }
+
+    /// Delays every request by the given duration.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = Some(latency);
+    }
+
+    /// Fails requests randomly with given rate between 0 and 1. Random numbers are generated from
+    /// the given seed, so the same seed always fails the same requests.
+    pub fn set_failure_rate(&mut self, rate: f64, seed: u64) {
+        self.failure_rate = rate;
+        // xorshift state must not be zero
+        *self.seed.lock().unwrap() = seed | 1;
+    }
+
+    /// Sets exact sequence of outcomes for the next requests, where `true` fails the request.
+    /// When the sequence is exhausted, failure rate is used.
+    pub fn set_failures<I: IntoIterator<Item=bool>>(&mut self, failures: I) {
+        *self.failures.lock().unwrap() = failures.into_iter().collect();
+    }
+
+    fn should_fail(&self) -> bool {
+        if let Some(fail) = self.failures.lock().unwrap().pop_front() {
+            return fail;
+        }
+
+        if self.failure_rate <= 0.0 {
+            return false;
+        }
+
+        // xorshift64*
+        let mut seed = self.seed.lock().unwrap();
+        *seed ^= *seed >> 12;
+        *seed ^= *seed << 25;
+        *seed ^= *seed >> 27;
+        let random = seed.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+
+        (random as f64 / (1u64 << 53) as f64) < self.failure_rate
+    }
+}
+
+impl<H: Hurl> Hurl for FaultyHurl<H> {
+    fn request(&self, req: Request) -> HurlResult {
+        let result: HurlResult = if self.should_fail() {
+            Box::new(future::err("injected failure".to_string()))
+        } else {
+            self.inner.request(req)
+        };
+
+        match self.latency {
+            Some(latency) => Box::new(Delay::new(Instant::now() + latency)
+                .map_err(|e| format!("timer error: {}", e))
+                .and_then(|_| result)),
+            None => result
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FaultyHurl;
+    use ::hurl::{Hurl, Request, Response, Method, HurlResult};
+    use futures::{self, Future};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+    use tokio::runtime::current_thread::Runtime;
+
+    struct OkHurl;
+
+    impl Hurl for OkHurl {
+        fn request(&self, _: Request) -> HurlResult {
+            Box::new(futures::future::ok(Response { status: 204, headers: HashMap::new(), body: String::new() }))
+        }
+    }
+
+    fn request() -> Request<'static> {
+        Request {
+            url: "http://localhost:8086/write",
+            method: Method::POST,
+            auth: None,
+            query: None,
+            headers: None,
+            body: None
+        }
+    }
+
+    fn outcomes<H: Hurl>(hurl: &H, n: usize) -> Vec<bool> {
+        (0..n).map(|_| hurl.request(request()).wait().is_err()).collect()
+    }
+
+    #[test]
+    fn test_failure_rate() {
+        let mut hurl = FaultyHurl::new(OkHurl);
+        hurl.set_failure_rate(0.5, 42);
+        let first = outcomes(&hurl, 1000);
+
+        let failures = first.iter().filter(|&&failed| failed).count();
+        assert!(failures > 400 && failures < 600, "failures: {}", failures);
+
+        let mut hurl = FaultyHurl::new(OkHurl);
+        hurl.set_failure_rate(0.5, 42);
+        assert_eq!(first, outcomes(&hurl, 1000));
+    }
+
+    #[test]
+    fn test_failures() {
+        let mut hurl = FaultyHurl::new(OkHurl);
+        hurl.set_failures(vec![true, false, true]);
+
+        assert_eq!(vec![true, false, true, false], outcomes(&hurl, 4));
+    }
+
+    #[test]
+    fn test_latency() {
+        let mut hurl = FaultyHurl::new(OkHurl);
+        hurl.set_latency(Duration::from_millis(50));
+
+        let start = Instant::now();
+        Runtime::new().unwrap().block_on(hurl.request(request())).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
@@ -1,97 +1,269 @@
 use hyper::Client as HyperClient;
 use hyper::Method as HyperMethod;
 use hyper::Request as HyperRequest;
+use hyper::Body;
+use hyper::client::connect::Connect;
 use http::header::AUTHORIZATION;
 use url::Url;
-use base64;
+use std::collections::HashMap;
 use futures::{self, Future, Stream};
 
-use super::{Request, Response, Method, HurlResult};
+use super::{Request, Response, Method, HurlResult, RESPONSE_TOO_LARGE};
 
 use super::Hurl;
 
+/// HTTP protocol version used to talk to InfluxDB.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Protocol {
+    /// HTTP/1.1, compatible with any InfluxDB version and proxy.
+    #[default]
+    Http1,
+    /// HTTP/2 with prior knowledge, which multiplexes concurrent writes over a single connection.
+    /// The server (or proxy in front of it) must accept cleartext HTTP/2.
+    Http2
+}
+
 #[derive(Default)]
-pub struct HyperHurl;
+pub struct HyperHurl {
+    protocol: Protocol,
+    max_response_size: Option<usize>
+}
 
 impl HyperHurl {
     pub fn new() -> HyperHurl {
         HyperHurl::default()
     }
+
+    /// Constructs new `HyperHurl` using given protocol version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::hurl::hyper::{HyperHurl, Protocol};
+    ///
+    /// let hurl = HyperHurl::with_protocol(Protocol::Http2);
+    /// ```
+    pub fn with_protocol(protocol: Protocol) -> HyperHurl {
+        HyperHurl { protocol, ..HyperHurl::default() }
+    }
+
+    /// Returns protocol version used by this hurl.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Sets the limit of the response body size in bytes. Reading of larger bodies is aborted, and
+    /// the request fails, so a misbehaving server could not exhaust the memory.
+    pub fn set_max_response_size(&mut self, size: usize) {
+        self.max_response_size = Some(size);
+    }
 }
 
 impl Hurl for HyperHurl {
     fn request(&self, req: Request) -> HurlResult {
-        let client = HyperClient::default();
-
-        // map request method to the hyper's
-        let method = match req.method {
-            Method::POST => HyperMethod::POST,
-            Method::GET  => HyperMethod::GET,
-        };
-
-        let mut url = match Url::parse(req.url) {
-            Ok(u) => { u }
-            Err(e) => {
-                return Box::new(futures::future::err(format!("could not parse url: {:?}", e)));
-            }
-        };
+        let client = HyperClient::builder()
+            .http2_only(self.protocol == Protocol::Http2)
+            .build_http::<Body>();
 
-        // if request has query
-        if let Some(ref query) = req.query {
-            // if any existing pairs
-            let existing: Vec<(String, String)> = url.query_pairs().map(|(a,b)| (a.to_string(), b.to_string())).collect();
+        send(&client, req, self.max_response_size)
+    }
+}
 
-            // final pairs
-            let mut pairs: Vec<(&str, &str)> = Vec::new();
+/// Sends `Request` with the given hyper client, so other hurls could reuse it with their own connectors.
+pub(crate) fn send<C>(client: &HyperClient<C>, req: Request, max_response_size: Option<usize>) -> HurlResult where C: Connect + 'static {
+    // map request method to the hyper's
+    let method = match req.method {
+        Method::POST => HyperMethod::POST,
+        Method::GET  => HyperMethod::GET,
+    };
 
-            // add first existing
-            for pair in &existing {
-                pairs.push((&pair.0, &pair.1));
-            }
+    let mut url = match Url::parse(req.url) {
+        Ok(u) => { u }
+        Err(e) => {
+            return Box::new(futures::future::err(format!("could not parse url: {:?}", e)));
+        }
+    };
 
-            // add given query to the pairs
-            for (key, val) in query.iter() {
-                pairs.push((key, val));
-            }
+    // if request has query
+    if let Some(ref query) = req.query {
+        // if any existing pairs
+        let existing: Vec<(String, String)> = url.query_pairs().map(|(a,b)| (a.to_string(), b.to_string())).collect();
 
-            // set new pairs
-            url.query_pairs_mut().clear().extend_pairs(
-                pairs.iter().map(|&(k, v)| { (&k[..], &v[..]) })
-            );
+        // final pairs
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+
+        // add first existing
+        for pair in &existing {
+            pairs.push((&pair.0, &pair.1));
+        }
+
+        // add given query to the pairs
+        for (key, val) in query.iter() {
+            pairs.push((key, val));
         }
 
-        // create query
-        let mut query = HyperRequest::builder();
-        query.method(method)
-            .uri(url.as_str());
+        // set new pairs
+        url.query_pairs_mut().clear().extend_pairs(
+            pairs.iter().map(|&(k, v)| { (k, v) })
+        );
+    }
+
+    // create query
+    let mut query = HyperRequest::builder();
+    query.method(method)
+        .uri(url.as_str());
+
+    // if request need to be authorized
+    if let Some(auth) = req.auth.as_ref().and_then(|auth| auth.header_value()) {
+        query.header(AUTHORIZATION, auth);
+    }
+
+    if let Some(ref headers) = req.headers {
+        for (name, value) in headers.iter() {
+            query.header(*name, &value[..]);
+        }
+    }
 
-        // if request need to be authorized
-        if let Some(auth) = req.auth {
-            let auth = base64::encode(&format!("{}:{}", auth.username, auth.password));
-            query.header(AUTHORIZATION, auth);
+    let request = match query.body(req.body.unwrap_or_default().into()) {
+        Ok(request) => request,
+        Err(e) => {
+            return Box::new(futures::future::err(format!("could not build request: {}", e)));
         }
+    };
+
+    Box::new(client
+        .request(request)
+        .map_err(|e| e.to_string())
+        .and_then(move |resp| {
+            let status = resp.status().as_u16();
+            let headers: HashMap<String, String> = resp.headers().iter().filter_map(|(name, value)| {
+                value.to_str().ok().map(|value| (name.as_str().to_string(), value.to_string()))
+            }).collect();
+
+            let body = resp.into_body().map_err(|e| e.to_string()).fold(Vec::new(), move |mut body, chunk| {
+                match max_response_size {
+                    Some(limit) if body.len() + chunk.len() > limit => {
+                        Err(format!("{} of {} bytes. Status: {}", RESPONSE_TOO_LARGE, limit, status))
+                    },
+                    _ => {
+                        body.extend_from_slice(&chunk);
+                        Ok(body)
+                    }
+                }
+            });
+
+            body.and_then(move |body| {
+                // no content is expected, so there is nothing to decode
+                if status == 204 {
+                    return Ok(Response {
+                        status,
+                        headers,
+                        body: String::new()
+                    });
+                }
 
-        let request = if let Some(body) = req.body {
-            query.body(body.into()).unwrap()
-        } else {
-            query.body("".into()).unwrap()
-        };
-
-        Box::new(client
-            .request(request)
-            .and_then(|resp| {
-                let status = resp.status().as_u16();
-
-                resp.into_body().concat2().and_then(move |body| {
-                    Ok(String::from_utf8(body.to_vec()).unwrap())
-                }).and_then(move |body|
-                    Ok(Response {
+                match String::from_utf8(body) {
+                    Ok(body) => Ok(Response {
                         status,
+                        headers,
                         body
-                    })
-                )
+                    }),
+                    Err(e) => Err(format!("could not decode response body. Status: {}; Error: {}", status, e))
+                }
             })
-            .map_err(|_| format!(""))
-        )
+        })
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HyperHurl, Protocol};
+    use ::hurl::{Hurl, Request, Method};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use tokio::runtime::current_thread::Runtime;
+
+    fn serve(response: &'static [u8]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/query", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+
+            while !received.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                received.extend_from_slice(&buf[..n]);
+            }
+
+            stream.write_all(response).unwrap();
+        });
+
+        url
+    }
+
+    fn get(url: &str) -> Request<'_> {
+        Request {
+            url,
+            method: Method::GET,
+            auth: None,
+            query: None,
+            headers: None,
+            body: None
+        }
+    }
+
+    #[test]
+    fn test_default_protocol() {
+        assert_eq!(Protocol::Http1, HyperHurl::new().protocol());
+    }
+
+    #[test]
+    fn test_with_protocol() {
+        assert_eq!(Protocol::Http1, HyperHurl::with_protocol(Protocol::Http1).protocol());
+        assert_eq!(Protocol::Http2, HyperHurl::with_protocol(Protocol::Http2).protocol());
+    }
+
+    #[test]
+    fn test_no_content() {
+        let url = serve(b"HTTP/1.1 204 No Content\r\nX-Influxdb-Version: 1.8.10\r\n\r\n");
+        let response = Runtime::new().unwrap().block_on(HyperHurl::new().request(get(&url))).unwrap();
+
+        assert_eq!(204, response.status);
+        assert_eq!(Some("1.8.10"), response.headers.get("x-influxdb-version").map(|s| &s[..]));
+        assert_eq!("", response.body);
+    }
+
+    #[test]
+    fn test_no_content_invalid_utf8_body() {
+        let url = serve(b"HTTP/1.1 204 No Content\r\nContent-Length: 2\r\n\r\n\xff\xfe");
+        let response = Runtime::new().unwrap().block_on(HyperHurl::new().request(get(&url))).unwrap();
+
+        assert_eq!(204, response.status);
+        assert_eq!("", response.body);
+    }
+
+    #[test]
+    fn test_invalid_utf8_body() {
+        let url = serve(b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 2\r\n\r\n\xff\xfe");
+        let error = Runtime::new().unwrap().block_on(HyperHurl::new().request(get(&url))).unwrap_err();
+
+        assert!(error.contains("Status: 500"), "{}", error);
+    }
+
+    #[test]
+    fn test_max_response_size() {
+        let mut hurl = HyperHurl::new();
+        hurl.set_max_response_size(10);
+
+        let url = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\n01234567890123456789");
+        let error = Runtime::new().unwrap().block_on(hurl.request(get(&url))).unwrap_err();
+        assert!(error.contains("limit of 10 bytes"), "{}", error);
+
+        let url = serve(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n0123456789");
+        let response = Runtime::new().unwrap().block_on(hurl.request(get(&url))).unwrap();
+        assert_eq!("0123456789", response.body);
     }
 }
@@ -1,10 +1,21 @@
 use std::collections::HashMap;
+use std::fmt;
 use futures::Future;
+use url::Url;
+use base64;
 
 pub mod hyper;
+#[cfg(feature = "testing")]
+pub mod faulty;
+#[cfg(all(unix, feature = "unix-socket"))]
+pub mod unix;
+
+/// Beginning of the error message of hurls, which aborted reading a response body larger than
+/// the configured limit. The request did reach the server, so it is not a communication error.
+pub const RESPONSE_TOO_LARGE: &str = "response body exceeds the limit";
 
 pub trait Hurl {
-    fn request(&self, Request) -> HurlResult;
+    fn request(&self, req: Request) -> HurlResult;
 }
 
 #[derive(Debug)]
@@ -13,22 +24,97 @@ pub struct Request<'a> {
     pub method: Method,
     pub auth: Option<Auth<'a>>,
     pub query: Option<HashMap<&'a str, String>>,
+    pub headers: Option<HashMap<&'a str, String>>,
     pub body: Option<String>
 }
 
+impl<'a> Request<'a> {
+    /// Renders the request as a `curl` command, with the password and values of credential headers,
+    /// like `Authorization`, redacted. Query parameters and headers are sorted by name, so the
+    /// output is stable.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::hurl::{Request, Method};
+    ///
+    /// let request = Request {
+    ///     url: "http://localhost:8086/ping",
+    ///     method: Method::GET,
+    ///     auth: None,
+    ///     query: None,
+    ///     headers: None,
+    ///     body: None
+    /// };
+    ///
+    /// assert_eq!("curl -X GET 'http://localhost:8086/ping'", request.to_curl());
+    /// ```
+    pub fn to_curl(&self) -> String {
+        let method = match self.method {
+            Method::POST => "POST",
+            Method::GET => "GET"
+        };
+
+        let url = match (Url::parse(self.url), self.query.as_ref()) {
+            (Ok(mut url), Some(query)) => {
+                let mut pairs: Vec<(&&str, &String)> = query.iter().collect();
+                pairs.sort();
+                url.query_pairs_mut().extend_pairs(pairs.into_iter().map(|(k, v)| (*k, &v[..])));
+                url.to_string()
+            },
+            _ => self.url.to_string()
+        };
+
+        let mut command = format!("curl -X {} {}", method, shell_quote(&url));
+
+        // mirror hurls, which send no credentials for empty ones
+        if let Some(auth) = self.auth.as_ref().filter(|auth| auth.header_value().is_some()) {
+            command.push_str(&format!(" -u {}", shell_quote(&format!("{}:<redacted>", auth.username))));
+        }
+
+        if let Some(ref headers) = self.headers {
+            let mut headers: Vec<(&&str, &String)> = headers.iter().collect();
+            headers.sort();
+
+            for (name, value) in headers {
+                let value = if is_credential_header(name) { "<redacted>" } else { &value[..] };
+                command.push_str(&format!(" -H {}", shell_quote(&format!("{}: {}", name, value))));
+            }
+        }
+
+        if let Some(ref body) = self.body {
+            command.push_str(&format!(" --data-binary {}", shell_quote(body)));
+        }
+
+        command
+    }
+}
+
+fn is_credential_header(name: &str) -> bool {
+    let name = name.to_lowercase();
+
+    name.ends_with("authorization") || name == "cookie" || name.contains("token") || name.contains("api-key")
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
 #[derive(Debug)]
 pub struct Response {
     pub status: u16,
+    /// Headers with lowercase names.
+    pub headers: HashMap<String, String>,
     pub body: String
 }
 
-impl ToString for Response {
-    fn to_string(&self) -> String {
-        self.body.clone()
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.body)
     }
 }
 
-pub type HurlResult = Box<Future<Item=Response, Error=String> + Send>;
+pub type HurlResult = Box<dyn Future<Item=Response, Error=String> + Send>;
 
 #[derive(Debug)]
 pub enum Method {
@@ -41,3 +127,89 @@ pub struct Auth<'a> {
     pub username: &'a str,
     pub password: &'a str
 }
+
+impl<'a> Auth<'a> {
+    /// Returns the value of the basic `Authorization` header, or `None` when both username and
+    /// password are empty. Either of them could be empty, e.g. when a proxy expects a token in the
+    /// password.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::hurl::Auth;
+    ///
+    /// let auth = Auth { username: "", password: "token" };
+    ///
+    /// assert_eq!(Some("Basic OnRva2Vu".to_string()), auth.header_value());
+    /// ```
+    pub fn header_value(&self) -> Option<String> {
+        if self.username.is_empty() && self.password.is_empty() {
+            return None;
+        }
+
+        Some(format!("Basic {}", base64::encode(format!("{}:{}", self.username, self.password))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Request, Method, Auth};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_to_curl() {
+        let mut query = HashMap::new();
+        query.insert("db", "test".to_string());
+        query.insert("precision", "s".to_string());
+
+        let mut headers = HashMap::new();
+        headers.insert("content-type", "text/plain".to_string());
+
+        let request = Request {
+            url: "http://localhost:8086/write",
+            method: Method::POST,
+            auth: Some(Auth { username: "gobwas", password: "secret" }),
+            query: Some(query),
+            headers: Some(headers),
+            body: Some("key,tag=it's value=1i 1".to_string())
+        };
+
+        let curl = request.to_curl();
+
+        assert_eq!(
+            "curl -X POST 'http://localhost:8086/write?db=test&precision=s' -u 'gobwas:<redacted>' -H 'content-type: text/plain' --data-binary 'key,tag=it'\\''s value=1i 1'",
+            curl
+        );
+        assert!(!curl.contains("secret"));
+    }
+
+    #[test]
+    fn test_to_curl_redacts_credentials() {
+        let mut headers = HashMap::new();
+        headers.insert("Authorization", "Token secret".to_string());
+        headers.insert("x-auth-token", "secret".to_string());
+        headers.insert("x-custom", "value".to_string());
+
+        let request = Request {
+            url: "http://localhost:8086/ping",
+            method: Method::GET,
+            auth: Some(Auth { username: "", password: "" }),
+            query: None,
+            headers: Some(headers),
+            body: None
+        };
+
+        assert_eq!(
+            "curl -X GET 'http://localhost:8086/ping' -H 'Authorization: <redacted>' -H 'x-auth-token: <redacted>' -H 'x-custom: value'",
+            request.to_curl()
+        );
+    }
+
+    #[test]
+    fn test_auth_header_value() {
+        assert_eq!(Some("Basic dXNlcjpwYXNz".to_string()), Auth { username: "user", password: "pass" }.header_value());
+        assert_eq!(Some("Basic dXNlcjo=".to_string()), Auth { username: "user", password: "" }.header_value());
+        assert_eq!(Some("Basic OnRva2Vu".to_string()), Auth { username: "", password: "token" }.header_value());
+        assert_eq!(None, Auth { username: "", password: "" }.header_value());
+    }
+}
@@ -0,0 +1,131 @@
+use hyper::Client as HyperClient;
+use hyper::Body;
+use hyper::client::connect::{Connect, Connected, Destination};
+use tokio::net::UnixStream;
+use tokio::net::unix::ConnectFuture;
+use futures::{Future, Poll};
+use std::io;
+use std::path::PathBuf;
+
+use super::{Request, HurlResult};
+use super::hyper::send;
+
+use super::Hurl;
+
+/// `Hurl` sending requests to InfluxDB listening on a Unix domain socket.
+///
+/// Every request is delivered to the configured socket, so only path and query of the request url
+/// are meaningful; host could be any valid http url, e.g. `http://localhost`.
+pub struct UnixHurl {
+    path: PathBuf
+}
+
+impl UnixHurl {
+    /// Constructs new `UnixHurl` connecting to the socket at given path.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::hurl::unix::UnixHurl;
+    ///
+    /// let hurl = UnixHurl::new("/var/run/influxdb.sock");
+    /// ```
+    pub fn new<P: Into<PathBuf>>(path: P) -> UnixHurl {
+        UnixHurl {
+            path: path.into()
+        }
+    }
+}
+
+impl Hurl for UnixHurl {
+    fn request(&self, req: Request) -> HurlResult {
+        let client = HyperClient::builder().build::<_, Body>(UnixConnector {
+            path: self.path.clone()
+        });
+
+        send(&client, req, None)
+    }
+}
+
+struct UnixConnector {
+    path: PathBuf
+}
+
+impl Connect for UnixConnector {
+    type Transport = UnixStream;
+    type Error = io::Error;
+    type Future = UnixConnecting;
+
+    fn connect(&self, _: Destination) -> Self::Future {
+        UnixConnecting(UnixStream::connect(&self.path))
+    }
+}
+
+struct UnixConnecting(ConnectFuture);
+
+impl Future for UnixConnecting {
+    type Item = (UnixStream, Connected);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let stream = try_ready!(self.0.poll());
+        Ok((stream, Connected::new()).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UnixHurl;
+    use ::hurl::{Hurl, Request, Method};
+    use std::collections::HashMap;
+    use std::env;
+    use std::fs;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixListener;
+    use std::process;
+    use std::thread;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn test_request() {
+        let path = env::temp_dir().join(format!("influent-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            let mut buf = [0; 1024];
+
+            while !String::from_utf8_lossy(&received).ends_with("cpu value=1") {
+                let n = stream.read(&mut buf).unwrap();
+                assert!(n > 0);
+                received.extend_from_slice(&buf[..n]);
+            }
+
+            stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap();
+            String::from_utf8(received).unwrap()
+        });
+
+        let mut query = HashMap::new();
+        query.insert("db", "test".to_string());
+
+        let hurl = UnixHurl::new(path.clone());
+        let request = Request {
+            url: "http://localhost/write",
+            method: Method::POST,
+            auth: None,
+            query: Some(query),
+            headers: None,
+            body: Some("cpu value=1".to_string())
+        };
+
+        let response = Runtime::new().unwrap().block_on(hurl.request(request)).unwrap();
+        let received = server.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(204, response.status);
+        assert!(received.starts_with("POST /write?db=test HTTP/1.1\r\n"));
+        assert!(received.ends_with("\r\n\r\ncpu value=1"));
+    }
+}
@@ -0,0 +1,467 @@
+#[cfg(feature = "serde_json")]
+use serde_json::{self, Value};
+
+/// Quotes InfluxQL identifier, like database or measurement name.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::quote_ident;
+///
+/// assert_eq!("\"my \\\"db\\\"\"", quote_ident("my \"db\""));
+/// ```
+pub fn quote_ident(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+/// Quotes InfluxQL string literal.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::quote_string;
+///
+/// assert_eq!("'it\\'s'", quote_string("it's"));
+/// ```
+pub fn quote_string(s: &str) -> String {
+    format!("'{}'", s.replace("\\", "\\\\").replace("'", "\\'"))
+}
+
+/// Placeholder of the time condition in queries passed to `split_time_range`.
+pub const TIME_FILTER: &str = "$timeFilter";
+
+/// Splits query over the nanosecond time range `[start, end)` into queries over consecutive
+/// windows of `window` nanoseconds, replacing `$timeFilter` with `time >= <from> AND time < <to>`.
+///
+/// Windows are half-open, so points on the boundary of two windows are returned by the later one
+/// only, and the last window is cut at `end`.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::split_time_range;
+///
+/// let queries = split_time_range("SELECT * FROM cpu WHERE $timeFilter", 0, 25, 10).unwrap();
+///
+/// assert_eq!(vec![
+///     "SELECT * FROM cpu WHERE time >= 0 AND time < 10",
+///     "SELECT * FROM cpu WHERE time >= 10 AND time < 20",
+///     "SELECT * FROM cpu WHERE time >= 20 AND time < 25"
+/// ], queries);
+/// ```
+pub fn split_time_range(query: &str, start: i64, end: i64, window: i64) -> Result<Vec<String>, String> {
+    if !query.contains(TIME_FILTER) {
+        return Err(format!("query has no {} placeholder", TIME_FILTER));
+    }
+
+    if window <= 0 {
+        return Err(format!("window must be positive, got {}", window));
+    }
+
+    let mut queries = vec![];
+    let mut from = start;
+
+    while from < end {
+        let to = from.saturating_add(window).min(end);
+        queries.push(query.replace(TIME_FILTER, &format!("time >= {} AND time < {}", from, to)));
+        from = to;
+    }
+
+    Ok(queries)
+}
+
+/// Iterator of page queries, returned by `paginate`.
+#[derive(Debug, Clone)]
+pub struct Pages {
+    base: String,
+    page_size: u64,
+    offset: u64,
+    end: Option<u64>
+}
+
+impl Iterator for Pages {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let limit = match self.end {
+            Some(end) if self.offset >= end => return None,
+            Some(end) => self.page_size.min(end - self.offset),
+            None => self.page_size
+        };
+
+        let query = format!("{} LIMIT {} OFFSET {}", self.base, limit, self.offset);
+        self.offset = self.offset.saturating_add(limit);
+
+        Some(query)
+    }
+}
+
+/// Splits query into queries of consecutive pages of `page_size` points, using `LIMIT` and
+/// `OFFSET` clauses. The iterator is endless, unless the query ends with its own `LIMIT`, which
+/// then caps the total number of points, and its `OFFSET` is where the first page starts.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::paginate;
+///
+/// let pages: Vec<String> = paginate("SELECT * FROM cpu LIMIT 25", 10).unwrap().collect();
+///
+/// assert_eq!(vec![
+///     "SELECT * FROM cpu LIMIT 10 OFFSET 0",
+///     "SELECT * FROM cpu LIMIT 10 OFFSET 10",
+///     "SELECT * FROM cpu LIMIT 5 OFFSET 20"
+/// ], pages);
+/// ```
+pub fn paginate(query: &str, page_size: u64) -> Result<Pages, String> {
+    if page_size == 0 {
+        return Err("page size must be positive".to_string());
+    }
+
+    let mut base = query.trim().trim_end_matches(';').trim_end();
+    let mut limit = None;
+    let mut offset = None;
+
+    loop {
+        let mut parts = base.rsplitn(3, char::is_whitespace);
+
+        let (value, clause, rest) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(value), Some(clause), Some(rest)) => (value, clause, rest),
+            _ => break
+        };
+
+        let value: u64 = match value.parse() {
+            Ok(value) => value,
+            Err(_) => break
+        };
+
+        if clause.eq_ignore_ascii_case("limit") && limit.is_none() {
+            limit = Some(value);
+        } else if clause.eq_ignore_ascii_case("offset") && offset.is_none() {
+            offset = Some(value);
+        } else {
+            break;
+        }
+
+        base = rest.trim_end();
+    }
+
+    let offset = offset.unwrap_or(0);
+
+    Ok(Pages {
+        base: base.to_string(),
+        page_size,
+        offset,
+        end: limit.map(|limit| offset.saturating_add(limit))
+    })
+}
+
+/// Continuous query definition.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::ContinuousQuery;
+///
+/// let mut cq = ContinuousQuery::new("cpu_1h", "telegraf", "SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h)");
+/// cq.resample_every = Some("30m");
+///
+/// assert_eq!(
+///     "CREATE CONTINUOUS QUERY \"cpu_1h\" ON \"telegraf\" RESAMPLE EVERY 30m BEGIN SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h) END",
+///     cq.create_statement()
+/// );
+/// ```
+#[derive(Debug)]
+pub struct ContinuousQuery<'a> {
+    /// Name of the continuous query.
+    pub name: &'a str,
+    /// Database the continuous query runs on.
+    pub database: &'a str,
+    /// `SELECT ... INTO ... GROUP BY time(...)` statement, used as is.
+    pub select: &'a str,
+    /// Duration literal of `RESAMPLE EVERY` clause, e.g. `30m`.
+    pub resample_every: Option<&'a str>,
+    /// Duration literal of `RESAMPLE FOR` clause, e.g. `2h`.
+    pub resample_for: Option<&'a str>
+}
+
+impl<'a> ContinuousQuery<'a> {
+    /// Constructs new `ContinuousQuery` without resampling.
+    pub fn new(name: &'a str, database: &'a str, select: &'a str) -> ContinuousQuery<'a> {
+        ContinuousQuery {
+            name,
+            database,
+            select,
+            resample_every: None,
+            resample_for: None
+        }
+    }
+
+    /// Returns `CREATE CONTINUOUS QUERY` statement.
+    pub fn create_statement(&self) -> String {
+        let mut statement = format!("CREATE CONTINUOUS QUERY {} ON {}", quote_ident(self.name), quote_ident(self.database));
+
+        if self.resample_every.is_some() || self.resample_for.is_some() {
+            statement.push_str(" RESAMPLE");
+
+            if let Some(every) = self.resample_every {
+                statement.push_str(" EVERY ");
+                statement.push_str(every);
+            }
+
+            if let Some(duration) = self.resample_for {
+                statement.push_str(" FOR ");
+                statement.push_str(duration);
+            }
+        }
+
+        format!("{} BEGIN {} END", statement, self.select)
+    }
+
+    /// Returns `DROP CONTINUOUS QUERY` statement.
+    pub fn drop_statement(&self) -> String {
+        format!("DROP CONTINUOUS QUERY {} ON {}", quote_ident(self.name), quote_ident(self.database))
+    }
+}
+
+/// Continuous query listed by `SHOW CONTINUOUS QUERIES`.
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContinuousQueryInfo {
+    /// Database the continuous query runs on.
+    pub database: String,
+    /// Name of the continuous query.
+    pub name: String,
+    /// `CREATE CONTINUOUS QUERY` statement, as stored by the server.
+    pub query: String
+}
+
+/// Parses response body of `SHOW CONTINUOUS QUERIES` into continuous queries of all databases.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::parse_continuous_queries;
+///
+/// let body = r#"{"results":[{"statement_id":0,"series":[{"name":"telegraf","columns":["name","query"],"values":[["cq","CREATE CONTINUOUS QUERY cq ON telegraf BEGIN SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h) END"]]}]}]}"#;
+/// let cqs = parse_continuous_queries(body).unwrap();
+///
+/// assert_eq!("telegraf", cqs[0].database);
+/// assert_eq!("cq", cqs[0].name);
+/// ```
+#[cfg(feature = "serde_json")]
+pub fn parse_continuous_queries(body: &str) -> Result<Vec<ContinuousQueryInfo>, String> {
+    let response: Value = serde_json::from_str(body).map_err(|e| format!("could not parse response: {}", e))?;
+
+    if let Some(error) = response["error"].as_str() {
+        return Err(error.to_string());
+    }
+
+    let results = response["results"].as_array().ok_or_else(|| "response has no results".to_string())?;
+    let mut cqs = vec![];
+
+    for result in results {
+        if let Some(error) = result["error"].as_str() {
+            return Err(error.to_string());
+        }
+
+        // databases without continuous queries have no series at all
+        let series = match result["series"].as_array() {
+            Some(series) => series,
+            None => continue
+        };
+
+        for series in series {
+            let database = series["name"].as_str().ok_or_else(|| "series has no name".to_string())?;
+            let columns = series["columns"].as_array().ok_or_else(|| format!("series \"{}\" has no columns", database))?;
+            let column = |name: &str| columns.iter().position(|column| column == name).ok_or_else(|| format!("series \"{}\" has no \"{}\" column", database, name));
+            let (name, query) = (column("name")?, column("query")?);
+
+            for row in series["values"].as_array().map(|values| &values[..]).unwrap_or(&[]) {
+                match (row[name].as_str(), row[query].as_str()) {
+                    (Some(name), Some(query)) => cqs.push(ContinuousQueryInfo {
+                        database: database.to_string(),
+                        name: name.to_string(),
+                        query: query.to_string()
+                    }),
+                    _ => return Err(format!("unexpected row {} of series \"{}\"", row, database))
+                }
+            }
+        }
+    }
+
+    Ok(cqs)
+}
+
+/// Splits response body of several statements, see `HttpClient::query_multi`, into results of
+/// each statement, ordered by `statement_id`. Fails if the result of any of `statements`
+/// statements is missing. Errors of single statements are kept in their results.
+///
+/// # Examples
+///
+/// ```
+/// use influent::influxql::results_by_statement;
+///
+/// let body = r#"{"results":[{"statement_id":1,"error":"database not found: db"},{"statement_id":0}]}"#;
+/// let results = results_by_statement(body, 2).unwrap();
+///
+/// assert_eq!(0, results[0]["statement_id"]);
+/// assert_eq!("database not found: db", results[1]["error"]);
+/// ```
+#[cfg(feature = "serde_json")]
+pub fn results_by_statement(body: &str, statements: usize) -> Result<Vec<Value>, String> {
+    let response: Value = serde_json::from_str(body).map_err(|e| format!("could not parse response: {}", e))?;
+
+    if let Some(error) = response["error"].as_str() {
+        return Err(error.to_string());
+    }
+
+    let results = response["results"].as_array().ok_or_else(|| "response has no results".to_string())?;
+    let mut by_statement = vec![None; statements];
+
+    for result in results {
+        let id = result["statement_id"].as_u64().ok_or_else(|| format!("result {} has no statement_id", result))?;
+
+        match by_statement.get_mut(id as usize) {
+            Some(slot) => *slot = Some(result.clone()),
+            None => return Err(format!("unexpected statement_id {}", id))
+        }
+    }
+
+    by_statement.into_iter().enumerate()
+        .map(|(id, result)| result.ok_or_else(|| format!("no result of statement {}", id)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContinuousQuery, quote_ident, quote_string, split_time_range, paginate};
+    #[cfg(feature = "serde_json")]
+    use super::{ContinuousQueryInfo, parse_continuous_queries, results_by_statement};
+
+    #[test]
+    fn test_quote_ident() {
+        assert_eq!("\"db\"", quote_ident("db"));
+        assert_eq!("\"a\\\\b\"", quote_ident("a\\b"));
+        assert_eq!("\"a\\\"b\"", quote_ident("a\"b"));
+    }
+
+    #[test]
+    fn test_quote_string() {
+        assert_eq!("'value'", quote_string("value"));
+        assert_eq!("'a\\\\b'", quote_string("a\\b"));
+        assert_eq!("'a\\'b'", quote_string("a'b"));
+    }
+
+    #[test]
+    fn test_create_statement() {
+        let mut cq = ContinuousQuery::new("cq \"1\"", "my db", "SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h)");
+        assert_eq!(
+            "CREATE CONTINUOUS QUERY \"cq \\\"1\\\"\" ON \"my db\" BEGIN SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h) END",
+            cq.create_statement()
+        );
+
+        cq.resample_every = Some("30m");
+        cq.resample_for = Some("2h");
+        assert_eq!(
+            "CREATE CONTINUOUS QUERY \"cq \\\"1\\\"\" ON \"my db\" RESAMPLE EVERY 30m FOR 2h BEGIN SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h) END",
+            cq.create_statement()
+        );
+
+        cq.resample_every = None;
+        assert_eq!(
+            "CREATE CONTINUOUS QUERY \"cq \\\"1\\\"\" ON \"my db\" RESAMPLE FOR 2h BEGIN SELECT mean(v) INTO m_1h FROM m GROUP BY time(1h) END",
+            cq.create_statement()
+        );
+    }
+
+    #[test]
+    fn test_drop_statement() {
+        let cq = ContinuousQuery::new("cq", "db", "SELECT 1");
+        assert_eq!("DROP CONTINUOUS QUERY \"cq\" ON \"db\"", cq.drop_statement());
+    }
+
+    #[test]
+    fn test_split_time_range() {
+        let queries = split_time_range("SELECT * FROM m WHERE $timeFilter", 100, 1000, 300).unwrap();
+        let bounds: Vec<(i64, i64)> = queries.iter().map(|q| {
+            let parts: Vec<&str> = q.split_whitespace().collect();
+            (parts[7].parse().unwrap(), parts[11].parse().unwrap())
+        }).collect();
+
+        assert_eq!(vec![(100, 400), (400, 700), (700, 1000)], bounds);
+        assert!(queries.iter().all(|q| q.contains(">= ") && q.contains("< ")));
+
+        assert_eq!(1, split_time_range("$timeFilter", 0, i64::MAX, i64::MAX).unwrap().len());
+        assert!(split_time_range("$timeFilter", 10, 10, 1).unwrap().is_empty());
+        assert!(split_time_range("$timeFilter", 0, 10, 0).is_err());
+        assert!(split_time_range("SELECT * FROM m", 0, 10, 1).is_err());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let pages: Vec<String> = paginate("SELECT * FROM m;", 100).unwrap().take(3).collect();
+        assert_eq!(vec![
+            "SELECT * FROM m LIMIT 100 OFFSET 0",
+            "SELECT * FROM m LIMIT 100 OFFSET 100",
+            "SELECT * FROM m LIMIT 100 OFFSET 200"
+        ], pages);
+
+        let pages: Vec<String> = paginate("SELECT * FROM m WHERE a = 'x  y' limit 15 offset 5", 10).unwrap().collect();
+        assert_eq!(vec![
+            "SELECT * FROM m WHERE a = 'x  y' LIMIT 10 OFFSET 5",
+            "SELECT * FROM m WHERE a = 'x  y' LIMIT 5 OFFSET 15"
+        ], pages);
+
+        assert_eq!(0, paginate("SELECT * FROM m LIMIT 0", 10).unwrap().count());
+        assert!(paginate("SELECT * FROM m", 0).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_parse_continuous_queries() {
+        let body = r#"{"results":[{"statement_id":0,"series":[
+            {"name":"_internal","columns":["name","query"]},
+            {"name":"telegraf","columns":["name","query"],"values":[
+                ["cpu_1h","CREATE CONTINUOUS QUERY cpu_1h ON telegraf BEGIN SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h) END"],
+                ["mem_1h","CREATE CONTINUOUS QUERY mem_1h ON telegraf BEGIN SELECT mean(used) INTO mem_1h FROM mem GROUP BY time(1h) END"]
+            ]}
+        ]}]}"#;
+
+        assert_eq!(vec![
+            ContinuousQueryInfo {
+                database: "telegraf".to_string(),
+                name: "cpu_1h".to_string(),
+                query: "CREATE CONTINUOUS QUERY cpu_1h ON telegraf BEGIN SELECT mean(usage) INTO cpu_1h FROM cpu GROUP BY time(1h) END".to_string()
+            },
+            ContinuousQueryInfo {
+                database: "telegraf".to_string(),
+                name: "mem_1h".to_string(),
+                query: "CREATE CONTINUOUS QUERY mem_1h ON telegraf BEGIN SELECT mean(used) INTO mem_1h FROM mem GROUP BY time(1h) END".to_string()
+            }
+        ], parse_continuous_queries(body).unwrap());
+
+        assert_eq!(Err("not authorized".to_string()), parse_continuous_queries(r#"{"results":[{"statement_id":0,"error":"not authorized"}]}"#));
+        assert!(parse_continuous_queries("not json").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_results_by_statement() {
+        let body = r#"{"results":[
+            {"statement_id":1,"series":[{"name":"cpu","columns":["time","value"],"values":[[0,1]]}]},
+            {"statement_id":0,"series":[{"name":"databases","columns":["name"],"values":[["db"]]}]},
+            {"statement_id":2,"error":"measurement not found"}
+        ]}"#;
+
+        let results = results_by_statement(body, 3).unwrap();
+        assert_eq!(3, results.len());
+        assert_eq!("databases", results[0]["series"][0]["name"]);
+        assert_eq!("cpu", results[1]["series"][0]["name"]);
+        assert_eq!("measurement not found", results[2]["error"]);
+
+        assert!(results_by_statement(body, 4).is_err());
+        assert!(results_by_statement(body, 2).is_err());
+        assert!(results_by_statement(r#"{"error":"not authorized"}"#, 1).is_err());
+    }
+}
@@ -1,15 +1,28 @@
 extern crate tokio;
 extern crate tokio_executor;
+#[cfg_attr(all(unix, feature = "unix-socket"), macro_use)]
 extern crate futures;
 extern crate http;
 extern crate base64;
 extern crate hyper;
 extern crate url;
+#[macro_use]
+extern crate log;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(feature = "derive")]
+extern crate influent_derive;
+#[cfg(any(test, feature = "serde_json"))]
+extern crate serde_json;
 
 pub mod client;
 pub mod hurl;
 pub mod serializer;
 pub mod measurement;
+pub mod influxql;
+pub mod schema;
 
 use client::Credentials;
 use client::http::HttpClient;
@@ -1,6 +1,19 @@
 use std::collections::BTreeMap;
 use std::borrow::Cow;
-#[derive(Debug)]
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use ::client::Precision;
+use ::serializer::line::{LineSerializer, ParseError};
+use base64;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde_json")]
+use serde_json;
+
+#[derive(Debug, Clone, PartialEq)]
 /// Measurement's field value.
 pub enum Value<'a> {
     /// String.
@@ -13,8 +26,81 @@ pub enum Value<'a> {
     Boolean(bool)
 }
 
+macro_rules! value_from {
+    ($variant:ident, $($t:ty),*) => {
+        $(
+            impl<'a> From<&'a $t> for Value<'a> {
+                fn from(value: &'a $t) -> Value<'a> {
+                    Value::$variant((*value).into())
+                }
+            }
+        )*
+    }
+}
+
+value_from!(Float, f64, f32);
+value_from!(Integer, i64, i32, i16, i8, u32, u16, u8);
+value_from!(Boolean, bool);
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(value: &'a str) -> Value<'a> {
+        Value::String(value)
+    }
+}
+
+impl<'a> From<&'a &'a str> for Value<'a> {
+    fn from(value: &'a &'a str) -> Value<'a> {
+        Value::String(value)
+    }
+}
+
+impl<'a> From<&'a String> for Value<'a> {
+    fn from(value: &'a String) -> Value<'a> {
+        Value::String(value)
+    }
+}
+
+/// Type of the field value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldType {
+    String,
+    Float,
+    Integer,
+    Boolean
+}
+
+impl<'a> Value<'a> {
+    /// Returns the type of the value.
+    pub fn field_type(&self) -> FieldType {
+        match *self {
+            Value::String(_) => FieldType::String,
+            Value::Float(_) => FieldType::Float,
+            Value::Integer(_) => FieldType::Integer,
+            Value::Boolean(_) => FieldType::Boolean
+        }
+    }
+
+    /// Compares values, allowing floats to differ by at most `epsilon`. Values of other types must
+    /// be equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Value;
+    ///
+    /// assert!(Value::Float(0.1 + 0.2).approx_eq(&Value::Float(0.3), 1e-9));
+    /// assert!(!Value::Integer(1).approx_eq(&Value::Float(1.0), 1e-9));
+    /// ```
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        match (self, other) {
+            (&Value::Float(a), &Value::Float(b)) => a == b || (a - b).abs() <= epsilon,
+            _ => self == other
+        }
+    }
+}
+
 /// Measurement model.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Measurement<'a> {
     /// Key.
     pub key: &'a str,
@@ -22,6 +108,11 @@ pub struct Measurement<'a> {
     /// Timestamp.
     pub timestamp: Option<i64>,
 
+    /// Precision of the timestamp. When set, the timestamp is converted to the precision of the
+    /// write request, because the write protocol allows only one precision per request. When not
+    /// set, the timestamp is assumed to be in the write precision already.
+    pub precision: Option<Precision>,
+
     /// Map of fields.
     pub fields: BTreeMap<Cow<'a, str>, Value<'a>>,
     /// Map of tags.
@@ -31,6 +122,9 @@ pub struct Measurement<'a> {
 impl<'a> Measurement<'a> {
     /// Constructs a new `Measurement`.
     ///
+    /// The key must not be empty, as InfluxDB rejects such points; this is checked in debug
+    /// builds only. Use `try_new` for keys coming from untrusted input.
+    ///
     /// # Examples
     ///
     /// ```
@@ -38,15 +132,37 @@ impl<'a> Measurement<'a> {
     ///
     /// let measurement = Measurement::new("key");
     /// ```
-    pub fn new(key: &str) -> Measurement {
+    pub fn new(key: &str) -> Measurement<'_> {
+        debug_assert!(!key.is_empty(), "measurement key must not be empty");
+
         Measurement {
-            key: key,
+            key,
             timestamp: None,
+            precision: None,
             fields: BTreeMap::new(),
             tags: BTreeMap::new()
         }
     }
 
+    /// Constructs a new `Measurement`, or returns error if the key is empty or consists of
+    /// whitespace only.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    ///
+    /// assert!(Measurement::try_new("key").is_ok());
+    /// assert!(Measurement::try_new(" ").is_err());
+    /// ```
+    pub fn try_new(key: &str) -> Result<Measurement<'_>, String> {
+        if key.trim().is_empty() {
+            return Err(format!("invalid measurement key \"{}\", expected non-blank string", key));
+        }
+
+        Ok(Measurement::new(key))
+    }
+
     /// Adds field to the measurement.
     ///
     /// # Examples
@@ -62,6 +178,51 @@ impl<'a> Measurement<'a> {
         self.fields.insert(field.into(), value);
     }
 
+    /// Adds field to the measurement like `add_field`, but returns error instead of overwriting
+    /// a field which is already set, to catch code paths setting the same field.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// assert!(measurement.try_add_field("field", Value::Integer(1)).is_ok());
+    /// assert!(measurement.try_add_field("field", Value::Integer(2)).is_err());
+    /// ```
+    pub fn try_add_field<T>(&mut self, field: T, value: Value<'a>) -> Result<(), String> where T: Into<Cow<'a, str>> {
+        let field = field.into();
+
+        if self.fields.contains_key(&field) {
+            return Err(format!("field \"{}\" of measurement \"{}\" is already set", field, self.key));
+        }
+
+        self.fields.insert(field, value);
+
+        Ok(())
+    }
+
+    /// Adds field to the measurement together with the companion tag `<field>_unit` holding its
+    /// unit, as InfluxDB has no field metadata.
+    ///
+    /// Every distinct unit creates a new series, so units should come from a small fixed set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let mut measurement = Measurement::new("weather");
+    ///
+    /// measurement.add_field_with_unit("temp", Value::Float(21.5), "celsius");
+    /// ```
+    pub fn add_field_with_unit<T>(&mut self, field: T, value: Value<'a>, unit: &'a str) where T: Into<Cow<'a, str>> {
+        let field = field.into();
+        self.add_tag(format!("{}_unit", field), unit);
+        self.add_field(field, value);
+    }
+
     /// Adds tag to the measurement.
     ///
     /// # Examples
@@ -77,6 +238,186 @@ impl<'a> Measurement<'a> {
         self.tags.insert(tag.into(), value.into());
     }
 
+    /// Adds tags from JSON object, which values must be strings.
+    ///
+    /// Returns error, adding nothing, if `json` is not an object or some value is not a string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// extern crate influent;
+    /// extern crate serde_json;
+    ///
+    /// use influent::measurement::Measurement;
+    ///
+    /// # fn main() {
+    /// let json: serde_json::Value = serde_json::from_str(r#"{"host": "server01"}"#).unwrap();
+    ///
+    /// let mut measurement = Measurement::new("key");
+    /// measurement.add_tags_from_json(&json).unwrap();
+    /// # }
+    /// ```
+    #[cfg(feature = "serde_json")]
+    pub fn add_tags_from_json(&mut self, json: &'a serde_json::Value) -> Result<(), String> {
+        let object = json.as_object().ok_or_else(|| format!("expected JSON object, got {}", json))?;
+
+        let mut tags = Vec::with_capacity(object.len());
+
+        for (tag, value) in object {
+            match *value {
+                serde_json::Value::String(ref value) => tags.push((tag, value)),
+                ref value => return Err(format!("tag \"{}\" must be a JSON string, got {}", tag, value))
+            }
+        }
+
+        for (tag, value) in tags {
+            self.add_tag(&tag[..], &value[..]);
+        }
+
+        Ok(())
+    }
+
+    /// Adds fields from JSON object. Strings, booleans and numbers are mapped to `Value::String`,
+    /// `Value::Boolean`, and `Value::Integer` or `Value::Float` depending on whether the number is
+    /// an integer. Nulls are skipped, as InfluxDB has no null values.
+    ///
+    /// Returns error, adding nothing, if `json` is not an object, or some value is an object, an
+    /// array, or an integer out of `i64` range.
+    #[cfg(feature = "serde_json")]
+    pub fn add_fields_from_json(&mut self, json: &'a serde_json::Value) -> Result<(), String> {
+        let object = json.as_object().ok_or_else(|| format!("expected JSON object, got {}", json))?;
+
+        let mut fields = Vec::with_capacity(object.len());
+
+        for (field, value) in object {
+            let value = match *value {
+                serde_json::Value::Null => continue,
+                serde_json::Value::Bool(b) => Value::Boolean(b),
+                serde_json::Value::String(ref s) => Value::String(s),
+                serde_json::Value::Number(ref n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+                    (Some(i), _, _) => Value::Integer(i),
+                    (None, Some(u), _) => return Err(format!("field \"{}\" value {} is out of integer range", field, u)),
+                    (None, None, Some(f)) => Value::Float(f),
+                    _ => return Err(format!("field \"{}\" has unsupported number {}", field, n))
+                },
+                ref value => return Err(format!("field \"{}\" must be a JSON string, number or boolean, got {}", field, value))
+            };
+
+            fields.push((field, value));
+        }
+
+        for (field, value) in fields {
+            self.add_field(&field[..], value);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the series key, that is the escaped key followed by tags sorted by key, as InfluxDB
+    /// computes it.
+    pub fn series_key(&self) -> String {
+        ::serializer::line::series_key(self)
+    }
+
+    /// Returns whether both measurements belong to the same series, i.e. have the same key and
+    /// tags, regardless of the order the tags were added in.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    ///
+    /// let mut a = Measurement::new("cpu");
+    /// a.add_tag("host", "a");
+    /// a.add_tag("region", "us-west");
+    ///
+    /// let mut b = Measurement::new("cpu");
+    /// b.add_tag("region", "us-west");
+    /// b.add_tag("host", "a");
+    ///
+    /// assert!(a.same_series(&b));
+    /// ```
+    pub fn same_series(&self, other: &Measurement) -> bool {
+        self.key == other.key && self.tags == other.tags
+    }
+
+    /// Returns hash of the measurement line protocol, that is of its key, tags, fields and
+    /// timestamp. It is computed with 64-bit FNV-1a, so it is stable across processes and builds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let mut a = Measurement::new("key");
+    /// a.add_field("field", Value::Integer(1));
+    ///
+    /// let b = a.clone();
+    /// assert_eq!(a.content_hash(), b.content_hash());
+    ///
+    /// a.add_field("field", Value::Integer(2));
+    /// assert_ne!(a.content_hash(), b.content_hash());
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+
+        // hashing never fails
+        write!(hasher, "{}", self).unwrap();
+
+        hasher.0
+    }
+
+    /// Returns the number of fields.
+    pub fn field_count(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Returns the number of tags.
+    pub fn tag_count(&self) -> usize {
+        self.tags.len()
+    }
+
+    /// Returns `true` if the measurement has no fields, so it could not be written.
+    pub fn is_empty_fields(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Returns pairs of tag keys which differ only by case or whitespace, like `Host` and `host`.
+    ///
+    /// InfluxDB treats such keys as distinct tags, which is rarely intended and increases series
+    /// cardinality. This check is not performed on write; call it where such mistakes are possible.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.add_tag("Host", "a");
+    /// measurement.add_tag("host", "b");
+    /// measurement.add_tag("region", "c");
+    ///
+    /// assert_eq!(vec![("Host", "host")], measurement.similar_tag_keys());
+    /// ```
+    pub fn similar_tag_keys(&self) -> Vec<(&str, &str)> {
+        let normalized: Vec<(&str, String)> = self.tags.keys().map(|tag| {
+            (&tag[..], tag.split_whitespace().collect::<String>().to_lowercase())
+        }).collect();
+
+        let mut similar = vec![];
+
+        for (i, &(tag, ref normal)) in normalized.iter().enumerate() {
+            for &(other, ref other_normal) in &normalized[i + 1..] {
+                if normal == other_normal {
+                    similar.push((tag, other));
+                }
+            }
+        }
+
+        similar
+    }
+
     /// Sets the timestamp of the measurement. It should be unix timestamp in nanosecond
     ///
     /// # Examples
@@ -90,5 +431,637 @@ impl<'a> Measurement<'a> {
     /// ```
     pub fn set_timestamp(&mut self, timestamp: i64) {
         self.timestamp = Some(timestamp);
+        self.precision = None;
+    }
+
+    /// Sets the timestamp of the measurement in the given precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::client::Precision;
+    /// use influent::measurement::Measurement;
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.set_timestamp_with_precision(1434055562, Precision::Seconds);
+    /// ```
+    pub fn set_timestamp_with_precision(&mut self, timestamp: i64, precision: Precision) {
+        self.timestamp = Some(timestamp);
+        self.precision = Some(precision);
+    }
+
+    /// Sets the timestamp of the measurement to the given system time, in nanoseconds precision.
+    ///
+    /// Returns error if the time could not be represented as nanoseconds since epoch in `i64`,
+    /// i.e. is before 1677 or after 2262.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.set_timestamp_from(UNIX_EPOCH + Duration::from_secs(1434055562)).unwrap();
+    ///
+    /// assert_eq!(Some(1434055562000000000), measurement.timestamp);
+    /// ```
+    pub fn set_timestamp_from(&mut self, time: SystemTime) -> Result<(), String> {
+        let timestamp = nanoseconds_since_epoch(time).ok_or_else(|| format!("time {:?} is out of timestamp range", time))?;
+        self.set_timestamp_with_precision(timestamp, Precision::Nanoseconds);
+        Ok(())
+    }
+
+    /// Sets the timestamp of the measurement to the given date time, in nanoseconds precision.
+    ///
+    /// Returns error if the date time is before 1677 or after 2262.
+    #[cfg(feature = "chrono")]
+    pub fn set_timestamp_from_datetime(&mut self, datetime: &DateTime<Utc>) -> Result<(), String> {
+        let timestamp = datetime.timestamp_nanos_opt().ok_or_else(|| format!("date time {} is out of timestamp range", datetime))?;
+        self.set_timestamp_with_precision(timestamp, Precision::Nanoseconds);
+        Ok(())
+    }
+
+    /// Sets the timestamp of the measurement to the current system time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.set_timestamp_now();
+    /// ```
+    pub fn set_timestamp_now(&mut self) {
+        self.set_timestamp_now_with(&SystemClock);
+    }
+
+    /// Sets the timestamp of the measurement to the current system time minus the given duration,
+    /// in nanoseconds precision. Durations reaching before 1677 saturate to the minimal timestamp.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::Measurement;
+    /// use std::time::Duration;
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.set_timestamp_ago(Duration::from_secs(5 * 60));
+    /// ```
+    pub fn set_timestamp_ago(&mut self, duration: Duration) {
+        let nanoseconds = i128::from(duration.as_secs()) * 1_000_000_000 + i128::from(duration.subsec_nanos());
+        let timestamp = (i128::from(SystemClock.now()) - nanoseconds).max(i128::from(i64::MIN));
+
+        self.set_timestamp_with_precision(timestamp as i64, Precision::Nanoseconds);
+    }
+
+    /// Sets the timestamp of the measurement to the current time of the given clock.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::measurement::{Measurement, FixedClock};
+    ///
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.set_timestamp_now_with(&FixedClock(1434055562000000000));
+    ///
+    /// assert_eq!(Some(1434055562000000000), measurement.timestamp);
+    /// ```
+    pub fn set_timestamp_now_with<C: Clock>(&mut self, clock: &C) {
+        self.set_timestamp_with_precision(clock.now(), Precision::Nanoseconds);
+    }
+}
+
+/// Formats measurement in line protocol, as `LineSerializer` with default settings does.
+///
+/// # Examples
+///
+/// ```
+/// use influent::measurement::{Measurement, Value};
+///
+/// let mut measurement = Measurement::new("key");
+/// measurement.add_field("field", Value::Integer(1));
+///
+/// assert_eq!("key field=1i", format!("{}", measurement));
+/// ```
+impl<'a> fmt::Display for Measurement<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        LineSerializer::new().write_to(self, f)
+    }
+}
+
+/// 64-bit FNV-1a hasher of formatted text.
+struct Fnv(u64);
+
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+
+        Ok(())
+    }
+}
+
+/// Conversion of a value into `Measurement` borrowing from it.
+///
+/// With the `derive` feature, it could be derived with `#[derive(ToMeasurement)]`, where the
+/// measurement name is set with `#[influx(measurement = "name")]` on the struct, and fields are
+/// marked with `#[influx(tag)]`, `#[influx(timestamp)]` or `#[influx(skip)]`. Other fields become
+/// measurement fields, and `Option` fields are omitted when `None`.
+pub trait ToMeasurement {
+    fn to_measurement(&self) -> Measurement<'_>;
+}
+
+#[cfg(feature = "derive")]
+pub use influent_derive::ToMeasurement;
+
+/// Template of measurements sharing the key and tags, e.g. written on every tick of a monitoring
+/// loop. Tags are set up once, and every produced measurement gets a copy of them.
+///
+/// # Examples
+///
+/// ```
+/// use influent::measurement::{MeasurementTemplate, Value};
+///
+/// let mut template = MeasurementTemplate::new("cpu");
+/// template.add_tag("host", "server01");
+///
+/// let measurement = template.build(Some(1434055562000000000), vec![("usage", Value::Float(0.64))]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MeasurementTemplate<'a> {
+    base: Measurement<'a>
+}
+
+impl<'a> MeasurementTemplate<'a> {
+    /// Constructs new `MeasurementTemplate` without tags.
+    pub fn new(key: &'a str) -> MeasurementTemplate<'a> {
+        MeasurementTemplate {
+            base: Measurement::new(key)
+        }
+    }
+
+    /// Adds tag shared by all measurements of the template.
+    pub fn add_tag<I, K>(&mut self, tag: I, value: K) where I: Into<Cow<'a,str>>, K: Into<Cow<'a, str>> {
+        self.base.add_tag(tag, value);
+    }
+
+    /// Produces measurement with the template key and tags, and the given timestamp and fields.
+    pub fn build<I, T>(&self, timestamp: Option<i64>, fields: I) -> Measurement<'a>
+        where I: IntoIterator<Item=(T, Value<'a>)>, T: Into<Cow<'a, str>>
+    {
+        let mut measurement = self.base.clone();
+        measurement.timestamp = timestamp;
+
+        for (field, value) in fields {
+            measurement.add_field(field, value);
+        }
+
+        measurement
+    }
+}
+
+/// Owned counterpart of `Value`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OwnedValue {
+    /// String.
+    String(String),
+    /// Floating point number.
+    Float(f64),
+    /// Integer number.
+    Integer(i64),
+    /// Boolean value.
+    Boolean(bool)
+}
+
+/// Owned counterpart of `Measurement`, which is not bound to the lifetime of the data it was built
+/// from. It could be persisted or sent elsewhere (with `serde` feature enabled) and then turned back
+/// into a `Measurement` for writing.
+///
+/// # Examples
+///
+/// ```
+/// use influent::measurement::{Measurement, OwnedMeasurement, Value};
+///
+/// let mut measurement = Measurement::new("key");
+/// measurement.add_field("field", Value::String("hello"));
+///
+/// let owned = OwnedMeasurement::from(&measurement);
+///
+/// assert_eq!("key", owned.as_measurement().key);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OwnedMeasurement {
+    /// Key.
+    pub key: String,
+
+    /// Timestamp.
+    pub timestamp: Option<i64>,
+
+    /// Precision of the timestamp.
+    pub precision: Option<Precision>,
+
+    /// Map of fields.
+    pub fields: BTreeMap<String, OwnedValue>,
+    /// Map of tags.
+    pub tags: BTreeMap<String, String>
+}
+
+impl OwnedMeasurement {
+    /// Borrows this as a `Measurement`.
+    pub fn as_measurement(&self) -> Measurement<'_> {
+        Measurement {
+            key: &self.key,
+            timestamp: self.timestamp,
+            precision: self.precision,
+            fields: self.fields.iter().map(|(field, value)| {
+                let value = match *value {
+                    OwnedValue::String(ref s) => Value::String(s),
+                    OwnedValue::Float(f) => Value::Float(f),
+                    OwnedValue::Integer(i) => Value::Integer(i),
+                    OwnedValue::Boolean(b) => Value::Boolean(b)
+                };
+
+                (Cow::Borrowed(&field[..]), value)
+            }).collect(),
+            tags: self.tags.iter().map(|(tag, value)| (Cow::Borrowed(&tag[..]), Cow::Borrowed(&value[..]))).collect()
+        }
+    }
+}
+
+/// Parses line protocol line, see `serializer::line::parse`.
+impl FromStr for OwnedMeasurement {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<OwnedMeasurement, ParseError> {
+        ::serializer::line::parse(s)
+    }
+}
+
+impl<'a, 'b> From<&'b Measurement<'a>> for OwnedMeasurement {
+    fn from(measurement: &'b Measurement<'a>) -> OwnedMeasurement {
+        OwnedMeasurement {
+            key: measurement.key.to_string(),
+            timestamp: measurement.timestamp,
+            precision: measurement.precision,
+            fields: measurement.fields.iter().map(|(field, value)| {
+                let value = match *value {
+                    Value::String(s) => OwnedValue::String(s.to_string()),
+                    Value::Float(f) => OwnedValue::Float(f),
+                    Value::Integer(i) => OwnedValue::Integer(i),
+                    Value::Boolean(b) => OwnedValue::Boolean(b)
+                };
+
+                (field.to_string(), value)
+            }).collect(),
+            tags: measurement.tags.iter().map(|(tag, value)| (tag.to_string(), value.to_string())).collect()
+        }
+    }
+}
+
+/// Source of the current time.
+pub trait Clock {
+    /// Returns current unix timestamp in nanoseconds.
+    fn now(&self) -> i64;
+}
+
+/// `Clock` reading the system time.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        nanoseconds_since_epoch(SystemTime::now()).expect("system time is out of timestamp range")
+    }
+}
+
+/// Returns nanoseconds since epoch of the given time, or `None` if it does not fit into `i64`.
+fn nanoseconds_since_epoch(time: SystemTime) -> Option<i64> {
+    let (duration, sign) = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d, 1),
+        Err(e) => (e.duration(), -1)
+    };
+
+    let nanoseconds = i128::from(duration.as_secs()) * 1_000_000_000 + i128::from(duration.subsec_nanos());
+    let nanoseconds = sign * nanoseconds;
+
+    if nanoseconds < i128::from(i64::MIN) || nanoseconds > i128::from(i64::MAX) {
+        None
+    } else {
+        Some(nanoseconds as i64)
+    }
+}
+
+/// `Clock` always returning the same nanosecond timestamp, useful for deterministic tests.
+#[derive(Debug)]
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
+
+/// Encodes binary data into a base64 string, so it could be stored as a `Value::String` field.
+///
+/// InfluxDB has no binary field type, so the data is written as a regular string field. Note that
+/// string fields are limited to 64KB and base64 encoding grows the payload by about a third, so
+/// this is only suitable for small blobs.
+///
+/// # Examples
+///
+/// ```
+/// use influent::measurement::{Measurement, Value, encode_bytes, decode_bytes};
+///
+/// let encoded = encode_bytes(&[0, 159, 146, 150]);
+///
+/// let mut measurement = Measurement::new("key");
+/// measurement.add_field("blob", Value::String(&encoded));
+///
+/// assert_eq!(vec![0, 159, 146, 150], decode_bytes(&encoded).unwrap());
+/// ```
+pub fn encode_bytes(bytes: &[u8]) -> String {
+    base64::encode(bytes)
+}
+
+/// Decodes binary data from a string field value, previously encoded by `encode_bytes`.
+pub fn decode_bytes(s: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    base64::decode(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Measurement, MeasurementTemplate, Value, Clock, SystemClock};
+    use super::OwnedMeasurement;
+    use ::client::Precision;
+    use ::serializer::Serializer;
+    use ::serializer::line::LineSerializer;
+    #[cfg(any(feature = "serde", feature = "serde_json"))]
+    use serde_json;
+    #[cfg(feature = "chrono")]
+    use chrono::{TimeZone, Utc};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_json_roundtrip() {
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("s", Value::String("string \"quoted\""));
+        measurement.add_field("i", Value::Integer(10));
+        measurement.add_field("f", Value::Float(10.5f64));
+        measurement.add_field("b", Value::Boolean(true));
+        measurement.add_tag("tag", "value");
+        measurement.set_timestamp_with_precision(1434055562, Precision::Seconds);
+
+        let json = serde_json::to_string(&OwnedMeasurement::from(&measurement)).unwrap();
+        let owned: OwnedMeasurement = serde_json::from_str(&json).unwrap();
+
+        let serializer = LineSerializer::new();
+        assert_eq!(serializer.serialize(&measurement), serializer.serialize(&owned.as_measurement()));
+        assert_eq!(Some(Precision::Seconds), owned.precision);
+    }
+
+    #[test]
+    fn test_set_timestamp_from() {
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("v", Value::Integer(1));
+
+        measurement.set_timestamp_from(UNIX_EPOCH + Duration::new(1434055562, 5)).unwrap();
+        assert_eq!(Some(1434055562000000005), measurement.timestamp);
+        assert_eq!(Some(Precision::Nanoseconds), measurement.precision);
+
+        measurement.set_timestamp_from(UNIX_EPOCH - Duration::new(1, 5)).unwrap();
+        assert_eq!(Some(-1000000005), measurement.timestamp);
+
+        assert!(measurement.set_timestamp_from(UNIX_EPOCH + Duration::from_secs(10_000_000_000)).is_err());
+        assert!(measurement.set_timestamp_from(UNIX_EPOCH - Duration::from_secs(10_000_000_000)).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "chrono")]
+    fn test_set_timestamp_from_datetime() {
+        let mut measurement = Measurement::new("key");
+
+        measurement.set_timestamp_from_datetime(&Utc.with_ymd_and_hms(2015, 6, 11, 20, 46, 2).unwrap()).unwrap();
+        assert_eq!(Some(1434055562000000000), measurement.timestamp);
+
+        measurement.set_timestamp_from_datetime(&Utc.with_ymd_and_hms(1969, 12, 31, 23, 59, 59).unwrap()).unwrap();
+        assert_eq!(Some(-1000000000), measurement.timestamp);
+
+        assert!(measurement.set_timestamp_from_datetime(&Utc.with_ymd_and_hms(2300, 1, 1, 0, 0, 0).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_set_timestamp_ago() {
+        let mut measurement = Measurement::new("key");
+
+        let before = SystemClock.now();
+        measurement.set_timestamp_ago(Duration::from_secs(300));
+        let after = SystemClock.now();
+
+        let timestamp = measurement.timestamp.unwrap();
+        assert!(timestamp >= before - 300_000_000_000 && timestamp <= after - 300_000_000_000);
+        assert_eq!(Some(Precision::Nanoseconds), measurement.precision);
+
+        measurement.set_timestamp_ago(Duration::from_secs(u64::MAX));
+        assert_eq!(Some(i64::MIN), measurement.timestamp);
+    }
+
+    #[test]
+    fn test_add_field_with_unit() {
+        let mut measurement = Measurement::new("weather");
+        measurement.add_field_with_unit("temp", Value::Float(21.5), "celsius");
+
+        assert_eq!("weather,temp_unit=celsius temp=21.5", LineSerializer::new().serialize(&measurement));
+    }
+
+    #[test]
+    fn test_template() {
+        let mut template = MeasurementTemplate::new("cpu");
+        template.add_tag("host", "server01");
+        template.add_tag("region", "us-west");
+
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_tag("host", "server01");
+        measurement.add_tag("region", "us-west");
+        measurement.add_field("usage", Value::Float(0.64));
+        measurement.add_field("idle", Value::Integer(10));
+        measurement.set_timestamp(1434055562000000000);
+
+        let serializer = LineSerializer::new();
+        let templated = template.build(Some(1434055562000000000), vec![("usage", Value::Float(0.64)), ("idle", Value::Integer(10))]);
+
+        assert_eq!(serializer.serialize(&measurement), serializer.serialize(&templated));
+        assert_eq!("cpu,host=server01,region=us-west value=1i", serializer.serialize(&template.build(None, vec![("value", Value::Integer(1))])));
+    }
+
+    #[test]
+    fn test_value_approx_eq() {
+        assert!(Value::Float(1.0).approx_eq(&Value::Float(1.0005), 0.001));
+        assert!(!Value::Float(1.0).approx_eq(&Value::Float(1.002), 0.001));
+        assert!(Value::Float(f64::INFINITY).approx_eq(&Value::Float(f64::INFINITY), 0.001));
+        assert!(!Value::Float(f64::NAN).approx_eq(&Value::Float(f64::NAN), 0.001));
+
+        assert!(Value::Integer(10).approx_eq(&Value::Integer(10), 0.001));
+        assert!(!Value::Integer(10).approx_eq(&Value::Integer(11), 100.0));
+        assert!(Value::String("a").approx_eq(&Value::String("a"), 0.001));
+        assert!(!Value::String("a").approx_eq(&Value::String("b"), 0.001));
+        assert!(!Value::Integer(1).approx_eq(&Value::Float(1.0), 0.001));
+
+        assert_eq!(Value::Boolean(true), Value::Boolean(true));
+        assert_ne!(Value::Integer(1), Value::Boolean(true));
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut measurement = Measurement::new("key");
+        assert_eq!(0, measurement.field_count());
+        assert_eq!(0, measurement.tag_count());
+        assert!(measurement.is_empty_fields());
+
+        measurement.add_tag("host", "a");
+        measurement.add_tag("region", "b");
+        measurement.add_field("value", Value::Integer(1));
+        assert_eq!(1, measurement.field_count());
+        assert_eq!(2, measurement.tag_count());
+        assert!(!measurement.is_empty_fields());
+
+        measurement.add_field("value", Value::Integer(2));
+        assert_eq!(1, measurement.field_count());
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_from_json() {
+        let tags: serde_json::Value = serde_json::from_str(r#"{"host": "server01", "region": "us-west"}"#).unwrap();
+        let fields: serde_json::Value = serde_json::from_str(r#"{"s": "hello", "i": 10, "f": 1.5, "b": true, "n": null}"#).unwrap();
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_tags_from_json(&tags).unwrap();
+        measurement.add_fields_from_json(&fields).unwrap();
+
+        assert_eq!("key,host=server01,region=us-west b=t,f=1.5,i=10i,s=\"hello\"", LineSerializer::new().serialize(&measurement));
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_from_json_rejects_nested() {
+        let nested: serde_json::Value = serde_json::from_str(r#"{"ok": 1, "nested": {"a": 1}}"#).unwrap();
+        let array: serde_json::Value = serde_json::from_str(r#"{"ok": "a", "array": [1, 2]}"#).unwrap();
+        let numeric_tag: serde_json::Value = serde_json::from_str(r#"{"tag": 1}"#).unwrap();
+        let big: serde_json::Value = serde_json::from_str(r#"{"big": 18446744073709551615}"#).unwrap();
+
+        let mut measurement = Measurement::new("key");
+        assert!(measurement.add_fields_from_json(&nested).is_err());
+        assert!(measurement.add_fields_from_json(&array).is_err());
+        assert!(measurement.add_fields_from_json(&big).is_err());
+        assert!(measurement.add_tags_from_json(&array).is_err());
+        assert!(measurement.add_tags_from_json(&numeric_tag).is_err());
+        assert!(measurement.add_fields_from_json(&serde_json::Value::Bool(true)).is_err());
+
+        assert!(measurement.fields.is_empty());
+        assert!(measurement.tags.is_empty());
+    }
+
+    #[test]
+    fn test_try_add_field() {
+        let mut measurement = Measurement::new("key");
+
+        assert!(measurement.try_add_field("field", Value::Integer(1)).is_ok());
+        assert!(measurement.try_add_field("field", Value::Integer(2)).is_err());
+        assert_eq!(Some(&Value::Integer(1)), measurement.fields.get("field"));
+
+        measurement.add_field("field", Value::Integer(3));
+        assert_eq!(Some(&Value::Integer(3)), measurement.fields.get("field"));
+    }
+
+    #[test]
+    fn test_display() {
+        let mut measurement = Measurement::new("cpu load");
+        measurement.add_tag("host", "a b");
+        measurement.add_field("value", Value::Float(0.5));
+        measurement.add_field("count", Value::Integer(2));
+        measurement.add_field("text", Value::String("say \"hi\""));
+        measurement.set_timestamp(1434055562000000000);
+
+        let displayed = format!("{}", measurement);
+
+        assert_eq!("cpu\\ load,host=a\\ b count=2i,text=\"say \\\"hi\\\"\",value=0.5 1434055562000000000", displayed);
+        assert_eq!(LineSerializer::new().serialize(&measurement), displayed);
+    }
+
+    #[test]
+    fn test_content_hash() {
+        let mut a = Measurement::new("key");
+        a.add_tag("host", "a");
+        a.add_field("value", Value::Float(0.5));
+        a.set_timestamp(1);
+
+        let mut b = Measurement::new("key");
+        b.set_timestamp(1);
+        b.add_field("value", Value::Float(0.5));
+        b.add_tag("host", "a");
+
+        assert_eq!(a.content_hash(), b.content_hash());
+
+        b.set_timestamp(2);
+        assert_ne!(a.content_hash(), b.content_hash());
+
+        let mut c = a.clone();
+        c.add_tag("host", "b");
+        assert_ne!(a.content_hash(), c.content_hash());
+    }
+
+    #[test]
+    fn test_same_series() {
+        let mut a = Measurement::new("cpu");
+        a.add_tag("a", "1");
+        a.add_tag("b", "2");
+        a.add_field("value", Value::Integer(1));
+
+        let mut b = Measurement::new("cpu");
+        b.add_tag("b", "2");
+        b.add_tag("a", "1");
+        b.add_field("other", Value::Integer(2));
+
+        assert!(a.same_series(&b));
+        assert_eq!(a.series_key(), b.series_key());
+
+        b.add_tag("c", "3");
+        assert!(!a.same_series(&b));
+        assert!(!a.same_series(&Measurement::new("mem")));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(Measurement::try_new("").is_err());
+        assert!(Measurement::try_new(" \t").is_err());
+        assert_eq!("cpu", Measurement::try_new("cpu").unwrap().key);
+        assert_eq!("cpu load", Measurement::try_new("cpu load").unwrap().key);
+    }
+
+    #[test]
+    #[should_panic(expected = "measurement key must not be empty")]
+    #[cfg(debug_assertions)]
+    fn test_new_empty_key() {
+        Measurement::new("");
+    }
+
+    #[test]
+    fn test_from_str() {
+        let parsed: OwnedMeasurement = "key value=1.5".parse().unwrap();
+        assert_eq!("key", parsed.key);
+
+        let error = "key value=NaN".parse::<OwnedMeasurement>().unwrap_err();
+        assert!(error.to_string().contains("\"NaN\" is not a number"), "{}", error);
     }
 }
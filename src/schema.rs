@@ -0,0 +1,90 @@
+use ::measurement::{Measurement, FieldType};
+use std::collections::HashMap;
+
+/// Expected field types of measurements, used to catch type drift before writing.
+///
+/// Only fields registered in the schema are checked; other fields and measurements are written
+/// as is.
+#[derive(Debug, Default)]
+pub struct Schema {
+    measurements: HashMap<String, HashMap<String, FieldType>>
+}
+
+impl Schema {
+    /// Constructs new empty `Schema`.
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Registers expected type of the field of given measurement.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::schema::Schema;
+    /// use influent::measurement::FieldType;
+    ///
+    /// let mut schema = Schema::new();
+    ///
+    /// schema.add_field("cpu", "load", FieldType::Float);
+    /// ```
+    pub fn add_field(&mut self, measurement: &str, field: &str, field_type: FieldType) {
+        self.measurements
+            .entry(measurement.to_string())
+            .or_default()
+            .insert(field.to_string(), field_type);
+    }
+
+    /// Checks that fields of the measurement have expected types.
+    pub fn validate(&self, measurement: &Measurement) -> Result<(), String> {
+        let fields = match self.measurements.get(measurement.key) {
+            Some(fields) => fields,
+            None => return Ok(())
+        };
+
+        for (field, value) in &measurement.fields {
+            if let Some(expected) = fields.get(&field[..]) {
+                if *expected != value.field_type() {
+                    return Err(format!("field \"{}\" of measurement \"{}\" is expected to be {:?}, got {:?}", field, measurement.key, expected, value.field_type()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Schema;
+    use ::measurement::{Measurement, Value, FieldType};
+
+    fn schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.add_field("cpu", "load", FieldType::Float);
+        schema.add_field("cpu", "cores", FieldType::Integer);
+        schema
+    }
+
+    #[test]
+    fn test_validate_conforming() {
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_field("load", Value::Float(0.5));
+        measurement.add_field("cores", Value::Integer(4));
+        measurement.add_field("unknown", Value::Boolean(true));
+
+        assert!(schema().validate(&measurement).is_ok());
+        assert!(schema().validate(&Measurement::new("memory")).is_ok());
+    }
+
+    #[test]
+    fn test_validate_mismatch() {
+        let mut measurement = Measurement::new("cpu");
+        measurement.add_field("load", Value::Integer(1));
+
+        assert_eq!(
+            Err("field \"load\" of measurement \"cpu\" is expected to be Float, got Integer".to_string()),
+            schema().validate(&measurement)
+        );
+    }
+}
@@ -1,8 +1,28 @@
-use ::measurement::{Measurement, Value};
+use ::measurement::{Measurement, Value, OwnedMeasurement, OwnedValue};
 use ::serializer::Serializer;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt::{self, Write};
+use std::iter::repeat_n;
+
+/// Line protocol escaping rules of the target InfluxDB version.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum EscapingMode {
+    /// Escaping of InfluxDB 1.x and later.
+    #[default]
+    Modern,
+    /// Escaping of InfluxDB 0.9 and 0.10, which do not unescape backslashes in string field
+    /// values, so they are written as is. Only backslashes which would escape the closing or an
+    /// inner quote are doubled, so such values are still read back with extra backslashes.
+    Legacy
+}
 
 #[derive(Default)]
-pub struct LineSerializer;
+pub struct LineSerializer {
+    integers_as_floats: bool,
+    escaping: EscapingMode
+}
 
 /// Line spec `Measurement` serializer.
 impl LineSerializer {
@@ -26,16 +46,381 @@ impl LineSerializer {
     pub fn new() -> LineSerializer {
         LineSerializer::default()
     }
+
+    /// Serializes integer fields as floats, so producers disagreeing on the field type do not
+    /// cause field type conflicts.
+    ///
+    /// Floats represent integers exactly only up to 2^53, so larger integers lose precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::serializer::Serializer;
+    /// use influent::serializer::line::LineSerializer;
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let mut serializer = LineSerializer::new();
+    /// serializer.set_integers_as_floats(true);
+    ///
+    /// let mut measurement = Measurement::new("key");
+    /// measurement.add_field("field", Value::Integer(10));
+    ///
+    /// assert_eq!("key field=10", serializer.serialize(&measurement));
+    /// ```
+    pub fn set_integers_as_floats(&mut self, enabled: bool) {
+        self.integers_as_floats = enabled;
+    }
+
+    /// Sets escaping rules of the target InfluxDB version. Defaults to `EscapingMode::Modern`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::serializer::Serializer;
+    /// use influent::serializer::line::{EscapingMode, LineSerializer};
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let mut serializer = LineSerializer::new();
+    /// serializer.set_escaping_mode(EscapingMode::Legacy);
+    ///
+    /// let mut measurement = Measurement::new("key");
+    /// measurement.add_field("path", Value::String("C:\\temp"));
+    ///
+    /// assert_eq!("key path=\"C:\\temp\"", serializer.serialize(&measurement));
+    /// ```
+    pub fn set_escaping_mode(&mut self, mode: EscapingMode) {
+        self.escaping = mode;
+    }
+
+    /// Writes measurement in line protocol into `out`, without allocating the whole line.
+    pub fn write_to<W: Write>(&self, measurement: &Measurement, out: &mut W) -> fmt::Result {
+        out.write_str(&escape_measurement(measurement.key))?;
+
+        for (tag, value) in &measurement.tags {
+            write!(out, ",{}={}", escape(tag), escape(value))?;
+        }
+
+        for (i, (field, value)) in measurement.fields.iter().enumerate() {
+            out.write_str(if i == 0 { " " } else { "," })?;
+            out.write_str(&escape(field))?;
+            out.write_char('=')?;
+
+            match *value {
+                Value::String(s) if self.escaping == EscapingMode::Legacy => out.write_str(&as_legacy_string(s))?,
+                Value::String(s)  => out.write_str(&as_string(s))?,
+                Value::Integer(i) if self.integers_as_floats => out.write_str(&as_float(&(i as f64)))?,
+                Value::Integer(ref i) => out.write_str(&as_integer(i))?,
+                Value::Float(ref f)   => out.write_str(&as_float(f))?,
+                Value::Boolean(ref b) => out.write_str(&as_boolean(b))?
+            };
+        }
+
+        if let Some(t) = measurement.timestamp {
+            write!(out, " {}", t)?;
+        }
+
+        Ok(())
+    }
 }
 
+/// Escapes measurement name. Equals sign is allowed there unescaped.
+fn escape_measurement(s: &str) -> String {
+    s
+        .replace(" ", "\\ ")
+        .replace(",", "\\,")
+}
+
+/// Escapes tag keys, tag values and field keys.
+///
+/// Backslashes are not escaped, as InfluxDB does not unescape them there. So a backslash at the
+/// end or before an escaped character could not be represented, and changes the parsed value.
 fn escape(s: &str) -> String {
     s
         .replace(" ", "\\ ")
         .replace(",", "\\,")
+        .replace("=", "\\=")
+}
+
+fn escaped_len(s: &str, special: &[char]) -> usize {
+    s.len() + s.chars().filter(|c| special.contains(c)).count()
+}
+
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+fn display_len<T: fmt::Display>(value: T) -> usize {
+    let mut counter = Counter(0);
+    write!(counter, "{}", value).unwrap();
+    counter.0
+}
+
+/// Returns the series key of the measurement, that is the escaped measurement name followed by its
+/// tags sorted by key, as InfluxDB computes it.
+///
+/// # Examples
+///
+/// ```
+/// use influent::serializer::line::series_key;
+/// use influent::measurement::Measurement;
+///
+/// let mut measurement = Measurement::new("cpu load");
+///
+/// measurement.add_tag("region", "us-west");
+/// measurement.add_tag("host", "a");
+///
+/// assert_eq!("cpu\\ load,host=a,region=us-west", series_key(&measurement));
+/// ```
+pub fn series_key(measurement: &Measurement) -> String {
+    let mut key = String::with_capacity(series_key_len(measurement));
+    key.push_str(&escape_measurement(measurement.key));
+
+    for (tag, value) in &measurement.tags {
+        key.push(',');
+        key.push_str(&escape(tag));
+        key.push('=');
+        key.push_str(&escape(value));
+    }
+
+    key
+}
+
+/// Returns the length of the series key of the measurement in bytes, that is the escaped
+/// measurement name followed by its tags, as they appear in line protocol.
+///
+/// # Examples
+///
+/// ```
+/// use influent::serializer::line::series_key_len;
+/// use influent::measurement::Measurement;
+///
+/// let mut measurement = Measurement::new("cpu");
+///
+/// measurement.add_tag("host", "a");
+///
+/// assert_eq!("cpu,host=a".len(), series_key_len(&measurement));
+/// ```
+pub fn series_key_len(measurement: &Measurement) -> usize {
+    let mut len = escaped_len(measurement.key, &[' ', ',']);
+
+    for (tag, value) in &measurement.tags {
+        len += 2 + escaped_len(tag, &[' ', ',', '=']) + escaped_len(value, &[' ', ',', '=']);
+    }
+
+    len
+}
+
+/// Error of parsing line protocol, returned by `parse`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    message: String
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses line protocol line into `OwnedMeasurement`. Timestamp is read as is, without precision.
+///
+/// Unsigned integers (with `u` suffix) are read as integers, when they fit into `i64`.
+///
+/// # Examples
+///
+/// ```
+/// use influent::serializer::line::parse;
+/// use influent::measurement::OwnedValue;
+///
+/// let measurement = parse("cpu,host=a usage=0.5,cores=8i,model=\"x86\" 1434055562000000000").unwrap();
+///
+/// assert_eq!("cpu", measurement.key);
+/// assert_eq!(Some(&OwnedValue::Integer(8)), measurement.fields.get("cores"));
+/// assert_eq!(Some(1434055562000000000), measurement.timestamp);
+/// ```
+pub fn parse(line: &str) -> Result<OwnedMeasurement, ParseError> {
+    parse_line(line).map_err(|message| ParseError { message })
+}
+
+fn parse_line(line: &str) -> Result<OwnedMeasurement, String> {
+    let end = scan(line, b", ");
+    let key = unescape(&line[..end], &[',', ' ']);
+    let mut rest = &line[end..];
+
+    if key.is_empty() {
+        return Err(format!("missing measurement name in \"{}\"", line));
+    }
+
+    let mut tags = BTreeMap::new();
+
+    while rest.starts_with(',') {
+        let (tag, tail) = read_key(&rest[1..], line)?;
+        let end = scan(tail, b", ");
+
+        if end == 0 {
+            return Err(format!("missing value of tag \"{}\" in \"{}\"", tag, line));
+        }
+
+        tags.insert(tag, unescape(&tail[..end], &[',', ' ', '=']));
+        rest = &tail[end..];
+    }
+
+    if !rest.starts_with(' ') {
+        return Err(format!("missing fields in \"{}\"", line));
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut separator = ' ';
+
+    while rest.starts_with(separator) {
+        separator = ',';
+
+        let (field, tail) = read_key(&rest[1..], line)?;
+        let (value, tail) = read_value(tail).map_err(|e| format!("invalid value of field \"{}\" in \"{}\": {}", field, line, e))?;
+
+        fields.insert(field, value);
+        rest = tail;
+    }
+
+    let timestamp = match rest {
+        "" => None,
+        _ if rest.starts_with(' ') => Some(rest[1..].parse().map_err(|e| format!("invalid timestamp in \"{}\": {}", line, e))?),
+        _ => return Err(format!("unexpected \"{}\" in \"{}\"", rest, line))
+    };
+
+    Ok(OwnedMeasurement {
+        key,
+        timestamp,
+        precision: None,
+        fields,
+        tags
+    })
+}
+
+/// Returns the index of the first unescaped delimiter, or the length of the string. All
+/// delimiters are ASCII, so the index is always on a char boundary.
+fn scan(s: &str, delimiters: &[u8]) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' {
+            i += 2;
+        } else if delimiters.contains(&bytes[i]) {
+            return i;
+        } else {
+            i += 1;
+        }
+    }
+
+    bytes.len()
+}
+
+fn unescape(s: &str, escaped: &[char]) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(next) if c == '\\' && escaped.contains(next) => {
+                result.push(*next);
+                chars.next();
+            },
+            _ => result.push(c)
+        }
+    }
+
+    result
+}
+
+/// Reads escaped key up to `=`, returning it with the rest of the string after `=`.
+fn read_key<'a>(s: &'a str, line: &str) -> Result<(String, &'a str), String> {
+    let end = scan(s, b"=, ");
+
+    if end == 0 || !s[end..].starts_with('=') {
+        return Err(format!("invalid key=value pair at \"{}\" in \"{}\"", s, line));
+    }
+
+    Ok((unescape(&s[..end], &[',', ' ', '=']), &s[end + 1..]))
+}
+
+fn read_value(s: &str) -> Result<(OwnedValue, &str), String> {
+    if let Some(quoted) = s.strip_prefix('"') {
+        let end = scan(quoted, b"\"");
+
+        if end == quoted.len() {
+            return Err("unterminated string".to_string());
+        }
+
+        return Ok((OwnedValue::String(unescape(&quoted[..end], &['"', '\\'])), &quoted[end + 1..]));
+    }
+
+    let end = scan(s, b", ");
+    let raw = &s[..end];
+
+    let value = match raw {
+        "t" | "T" | "true" | "True" | "TRUE" => OwnedValue::Boolean(true),
+        "f" | "F" | "false" | "False" | "FALSE" => OwnedValue::Boolean(false),
+        _ if raw.ends_with('i') => OwnedValue::Integer(raw[..raw.len() - 1].parse().map_err(|e| format!("{}", e))?),
+        _ if raw.ends_with('u') => {
+            let value: u64 = raw[..raw.len() - 1].parse().map_err(|e| format!("{}", e))?;
+            OwnedValue::Integer(i64::try_from(value).map_err(|_| format!("unsigned integer {} does not fit into i64", value))?)
+        },
+        // f64 parsing accepts inf and NaN, which line protocol has no representation for
+        _ if !raw.bytes().all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b)) => return Err(format!("\"{}\" is not a number", raw)),
+        _ => {
+            let value: f64 = raw.parse().map_err(|e| format!("{}", e))?;
+
+            if !value.is_finite() {
+                return Err(format!("{} is out of range", raw));
+            }
+
+            OwnedValue::Float(value)
+        }
+    };
+
+    Ok((value, &s[end..]))
 }
 
 fn as_string(s: &str) -> String {
-    format!("\"{}\"", s.replace("\"", "\\\""))
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+fn as_legacy_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    let mut backslashes = 0;
+
+    for c in s.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                continue;
+            },
+            '"' => {
+                out.extend(repeat_n('\\', backslashes * 2));
+                out.push_str("\\\"");
+            },
+            c => {
+                out.extend(repeat_n('\\', backslashes));
+                out.push(c);
+            }
+        }
+
+        backslashes = 0;
+    }
+
+    out.extend(repeat_n('\\', backslashes * 2));
+    out.push('"');
+    out
 }
 
 fn as_integer(i: &i64) -> String {
@@ -52,44 +437,60 @@ fn as_boolean(b: &bool) -> String {
 
 impl Serializer for LineSerializer {
     fn serialize(&self, measurement: &Measurement) -> String {
-        let mut line = vec![escape(measurement.key)];
+        let mut line = String::with_capacity(self.serialized_len(measurement));
 
-        for (tag, value) in &measurement.tags {
-            line.push(",".to_string());
-            line.push(escape(tag));
-            line.push("=".to_string());
-            line.push(escape(value));
-        }
+        // writing into String never fails
+        self.write_to(measurement, &mut line).unwrap();
 
-        let mut was_spaced = false;
+        line
+    }
 
-        for (field, value) in &measurement.fields {
-            line.push({if !was_spaced { was_spaced = true; " " } else { "," }}.to_string());
-            line.push(escape(field));
-            line.push("=".to_string());
+    /// Computes the exact length of the serialized measurement without allocating it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use influent::serializer::Serializer;
+    /// use influent::serializer::line::LineSerializer;
+    /// use influent::measurement::{Measurement, Value};
+    ///
+    /// let serializer = LineSerializer::new();
+    /// let mut measurement = Measurement::new("key");
+    ///
+    /// measurement.add_field("field", Value::String("value"));
+    /// measurement.add_tag("tag", "value");
+    ///
+    /// assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+    /// ```
+    fn serialized_len(&self, measurement: &Measurement) -> usize {
+        let mut len = series_key_len(measurement);
 
-            match *value {
-                Value::String(s)  => line.push(as_string(s)),
-                Value::Integer(ref i) => line.push(as_integer(i)),
-                Value::Float(ref f)   => line.push(as_float(f)),
-                Value::Boolean(ref b) => line.push(as_boolean(b))
+        for (field, value) in &measurement.fields {
+            len += 2 + escaped_len(field, &[' ', ',', '=']);
+
+            len += match *value {
+                Value::String(s) if self.escaping == EscapingMode::Legacy => as_legacy_string(s).len(),
+                Value::String(s)  => 2 + escaped_len(s, &['"', '\\']),
+                Value::Integer(i) if self.integers_as_floats => display_len(i as f64),
+                Value::Integer(i) => 1 + display_len(i),
+                Value::Float(f)   => display_len(f),
+                Value::Boolean(_) => 1
             };
         }
 
         if let Some(t) = measurement.timestamp {
-                line.push(" ".to_string());
-                line.push(t.to_string());
+            len += 1 + display_len(t);
         }
 
-        line.join("")
+        len
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{as_boolean, as_string, as_integer, as_float, escape, LineSerializer};
+    use super::{as_boolean, as_string, as_legacy_string, as_integer, as_float, escape, escape_measurement, series_key, parse, EscapingMode, LineSerializer};
     use ::serializer::Serializer;
-    use ::measurement::{Measurement, Value};
+    use ::measurement::{Measurement, Value, OwnedValue};
 
     #[test]
     fn test_as_boolean() {
@@ -99,7 +500,31 @@ mod tests {
 
     #[test]
     fn test_as_string() {
-        assert_eq!("\"\\\"hello\\\"\"", as_string(&"\"hello\""));
+        assert_eq!("\"\\\"hello\\\"\"", as_string("\"hello\""));
+        assert_eq!("\"a\\\\\"", as_string("a\\"));
+        assert_eq!("\"\\\\\\\"\"", as_string("\\\""));
+    }
+
+    #[test]
+    fn test_as_legacy_string() {
+        assert_eq!("\"\\\"hello\\\"\"", as_legacy_string("\"hello\""));
+        assert_eq!("\"a\\b\"", as_legacy_string("a\\b"));
+        assert_eq!("\"a\\\\\"", as_legacy_string("a\\"));
+        assert_eq!("\"a\\\\\\\"\"", as_legacy_string("a\\\""));
+    }
+
+    #[test]
+    fn test_escaping_modes() {
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("s", Value::String("a\\b \"c\""));
+
+        let mut serializer = LineSerializer::new();
+        assert_eq!("key s=\"a\\\\b \\\"c\\\"\"", serializer.serialize(&measurement));
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+
+        serializer.set_escaping_mode(EscapingMode::Legacy);
+        assert_eq!("key s=\"a\\b \\\"c\\\"\"", serializer.serialize(&measurement));
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
     }
 
     #[test]
@@ -111,6 +536,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_as_float() {
         assert_eq!("1", as_float(&1f64));
         assert_eq!("1", as_float(&1.0f64));
@@ -123,6 +549,54 @@ mod tests {
         assert_eq!("\\ ", escape(" "));
         assert_eq!("\\,", escape(","));
         assert_eq!("hello\\,\\ gobwas", escape("hello, gobwas"));
+        assert_eq!("\\=", escape("="));
+        assert_eq!("a\\=b", escape("a=b"));
+    }
+
+    #[test]
+    fn test_escape_measurement() {
+        assert_eq!("\\ ", escape_measurement(" "));
+        assert_eq!("\\,", escape_measurement(","));
+        assert_eq!("=", escape_measurement("="));
+        assert_eq!("a=b\\,\\ c", escape_measurement("a=b, c"));
+    }
+
+    #[test]
+    fn test_line_serializer_escaping() {
+        let serializer = LineSerializer::new();
+        let cases = [
+            (" ", "\\ ", "\\ "),
+            (",", "\\,", "\\,"),
+            ("=", "=",   "\\="),
+        ];
+
+        for &(raw, in_measurement, in_key) in &cases {
+            let name = format!("a{}b", raw);
+
+            let mut measurement = Measurement::new(&name);
+            measurement.add_tag("tag", "value");
+            measurement.add_field("field", Value::Integer(1));
+            assert_eq!(format!("a{}b,tag=value field=1i", in_measurement), serializer.serialize(&measurement));
+
+            let mut measurement = Measurement::new("key");
+            measurement.add_tag(name.as_str(), "value");
+            measurement.add_field("field", Value::Integer(1));
+            assert_eq!(format!("key,a{}b=value field=1i", in_key), serializer.serialize(&measurement));
+
+            let mut measurement = Measurement::new("key");
+            measurement.add_tag("tag", name.as_str());
+            measurement.add_field("field", Value::Integer(1));
+            assert_eq!(format!("key,tag=a{}b field=1i", in_key), serializer.serialize(&measurement));
+
+            let mut measurement = Measurement::new("key");
+            measurement.add_tag("tag", "value");
+            measurement.add_field(name.as_str(), Value::Integer(1));
+            assert_eq!(format!("key,tag=value a{}b=1i", in_key), serializer.serialize(&measurement));
+
+            let mut measurement = Measurement::new("key");
+            measurement.add_field("field", Value::String(&name));
+            assert_eq!(format!("key field=\"{}\"", name), serializer.serialize(&measurement));
+        }
     }
 
     #[test]
@@ -157,8 +631,195 @@ mod tests {
 
         assert_eq!("key s=\"string\" 1434055562000000000", serializer.serialize(&measurement));
     }
-}
 
+    #[test]
+    fn test_serialized_len() {
+        let serializer = LineSerializer::new();
 
+        let mut measurement = Measurement::new("key, with=escapes");
+        measurement.add_tag("tag, with=escapes", "value, with=escapes");
+        measurement.add_tag("другой", "тег");
+        measurement.add_field("s", Value::String("\"quoted\", string"));
+        measurement.add_field("i", Value::Integer(-10));
+        measurement.add_field("f", Value::Float(-10.25f64));
+        measurement.add_field("b", Value::Boolean(true));
+        measurement.set_timestamp(-1434055562000000000);
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
 
+        let mut measurement = Measurement::new("key");
+        measurement.add_tag("tag", "value");
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+
+        let measurement = Measurement::new("key");
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+    }
+
+    #[test]
+    fn test_line_serializer_utf8() {
+        let serializer = LineSerializer::new();
+        let mut measurement = Measurement::new("温度 🌡");
+
+        measurement.add_tag("城市, 区", "東京=🗼");
+        measurement.add_field("状態 🙂", Value::String("\"良い\" 👍"));
 
+        assert_eq!("温度\\ 🌡,城市\\,\\ 区=東京\\=🗼 状態\\ 🙂=\"\\\"良い\\\" 👍\"", serializer.serialize(&measurement));
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+    }
+
+    #[test]
+    fn test_line_serializer_integers_as_floats() {
+        let mut serializer = LineSerializer::new();
+        serializer.set_integers_as_floats(true);
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("i", Value::Integer(-10));
+        measurement.add_field("f", Value::Float(1.5));
+
+        assert_eq!("key f=1.5,i=-10", serializer.serialize(&measurement));
+        assert_eq!(serializer.serialize(&measurement).len(), serializer.serialized_len(&measurement));
+    }
+
+    #[test]
+    fn test_line_serializer_no_fields() {
+        let serializer = LineSerializer::new();
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_tag("tag", "value");
+
+        assert!(serializer.try_serialize(&measurement).is_err());
+
+        measurement.add_field("field", Value::Integer(1));
+        assert_eq!(Ok("key,tag=value field=1i".to_string()), serializer.try_serialize(&measurement));
+    }
+
+    /// Returns whether InfluxDB could read the string back from a key or tag, i.e. it has no
+    /// backslash at the end or before a character which gets escaped.
+    fn representable(s: &str) -> bool {
+        !s.ends_with('\\') && !s.contains("\\ ") && !s.contains("\\,") && !s.contains("\\=")
+    }
+
+    #[test]
+    fn test_line_serializer_escaping_roundtrip() {
+        let serializer = LineSerializer::new();
+        let alphabet = ['a', 'é', ' ', ',', '=', '"', '\\', '\n'];
+        let mut seed: u64 = 42;
+
+        let mut random = || {
+            // xorshift64*
+            seed ^= seed >> 12;
+            seed ^= seed << 25;
+            seed ^= seed >> 27;
+            (seed.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 33) as usize
+        };
+
+        let mut cases: Vec<String> = vec![
+            "a\\".to_string(),
+            "\\\"".to_string(),
+            "\"\\".to_string(),
+            "a\\\\b".to_string(),
+            "\\n".to_string(),
+        ];
+
+        for _ in 0..2000 {
+            let len = 1 + random() % 8;
+            cases.push((0..len).map(|_| alphabet[random() % alphabet.len()]).collect());
+        }
+
+        for case in &cases {
+            // newlines terminate lines, and could not be written anywhere but string fields
+            let name = if case.contains('\n') || !representable(case) || case.starts_with(' ') { "key" } else { &case[..] };
+            let key = if case.contains('\n') || !representable(case) { "key" } else { &case[..] };
+
+            let mut measurement = Measurement::new(name);
+            measurement.add_tag(key, key);
+            measurement.add_field(key, Value::String(case));
+
+            let line = serializer.serialize(&measurement);
+            assert_eq!(line.len(), serializer.serialized_len(&measurement));
+            let parsed = parse(&line).unwrap();
+            assert_eq!(name, parsed.key, "case: {:?}, line: {:?}", case, line);
+            assert_eq!(Some(key), parsed.tags.get(key).map(|s| &s[..]), "case: {:?}, line: {:?}", case, line);
+            assert_eq!(Some(&OwnedValue::String(case.clone())), parsed.fields.get(key), "case: {:?}, line: {:?}", case, line);
+        }
+    }
+
+    #[test]
+    fn test_series_key() {
+        let mut measurement = Measurement::new("cpu");
+        assert_eq!("cpu", series_key(&measurement));
+
+        measurement.add_tag("zone", "a b");
+        measurement.add_tag("host", "server=01");
+        measurement.add_tag("Host", "x,y");
+        measurement.add_field("value", Value::Integer(1));
+        measurement.set_timestamp(1);
+
+        assert_eq!("cpu,Host=x\\,y,host=server\\=01,zone=a\\ b", series_key(&measurement));
+        assert_eq!(series_key(&measurement), measurement.series_key());
+    }
+
+    #[test]
+    fn test_parse_roundtrip() {
+        let serializer = LineSerializer::new();
+
+        let mut measurement = Measurement::new("cpu load,total");
+        measurement.add_tag("host name", "a=b,c");
+        measurement.add_tag("région", "é");
+        measurement.add_field("s", Value::String("say \"hi\" \\ bye, friend"));
+        measurement.add_field("i", Value::Integer(-10));
+        measurement.add_field("f", Value::Float(10.5));
+        measurement.add_field("b", Value::Boolean(false));
+        measurement.add_field("eq=ual", Value::Boolean(true));
+        measurement.set_timestamp(-1434055562000000000);
+
+        let line = serializer.serialize(&measurement);
+        let parsed = parse(&line).unwrap();
+
+        assert_eq!("cpu load,total", parsed.key);
+        assert_eq!(Some("a=b,c"), parsed.tags.get("host name").map(|s| &s[..]));
+        assert_eq!(Some(&OwnedValue::String("say \"hi\" \\ bye, friend".to_string())), parsed.fields.get("s"));
+        assert_eq!(line, serializer.serialize(&parsed.as_measurement()));
+
+        let mut measurement = Measurement::new("key");
+        measurement.add_field("value", Value::Integer(1));
+        assert_eq!(serializer.serialize(&measurement), serializer.serialize(&parse("key value=1i").unwrap().as_measurement()));
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let parsed = parse("key a=1u,b=T,c=false,d=1e3,e=\"\",f=-0.5").unwrap();
+
+        assert_eq!(Some(&OwnedValue::Integer(1)), parsed.fields.get("a"));
+        assert_eq!(Some(&OwnedValue::Boolean(true)), parsed.fields.get("b"));
+        assert_eq!(Some(&OwnedValue::Boolean(false)), parsed.fields.get("c"));
+        assert_eq!(Some(&OwnedValue::Float(1000.0)), parsed.fields.get("d"));
+        assert_eq!(Some(&OwnedValue::String(String::new())), parsed.fields.get("e"));
+        assert_eq!(Some(&OwnedValue::Float(-0.5)), parsed.fields.get("f"));
+        assert_eq!(None, parsed.timestamp);
+        assert!(parsed.tags.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for line in &[
+            "",
+            "key",
+            "key,tag=value",
+            ",tag=value value=1",
+            "key,tag value=1",
+            "key,tag= value=1",
+            "key value=",
+            "key value=1x",
+            "key value=\"unterminated",
+            "key value=18446744073709551615u",
+            "key value=inf",
+            "key value=-Infinity",
+            "key value=NaN",
+            "key value=1e999",
+            "key value=1 now",
+            "key value=1 1 2",
+        ] {
+            assert!(parse(line).is_err(), "line: {:?}", line);
+        }
+    }
+}
@@ -5,5 +5,24 @@ pub mod line;
 /// `Measurement` serializer.
 pub trait Serializer {
     /// Serializes measurement to String.
+    ///
+    /// Measurements without fields are not valid points, so their output is rejected by InfluxDB;
+    /// use `try_serialize` to catch them.
     fn serialize(&self, measurement: &Measurement) -> String;
+
+    /// Serializes measurement to String, or returns error if it has no fields.
+    fn try_serialize(&self, measurement: &Measurement) -> Result<String, String> {
+        if measurement.fields.is_empty() {
+            return Err(format!("measurement \"{}\" has no fields", measurement.key));
+        }
+
+        Ok(self.serialize(measurement))
+    }
+
+    /// Returns the length of serialized measurement in bytes, used to pre-size buffers.
+    ///
+    /// It is allowed to be an estimate; the default implementation returns zero.
+    fn serialized_len(&self, _measurement: &Measurement) -> usize {
+        0
+    }
 }
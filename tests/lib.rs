@@ -9,11 +9,11 @@ use influent::measurement::{Measurement, Value};
 use futures::Future;
 use std::sync::Arc;
 
-fn before<'a>() -> HttpClient<'a> {
+fn before(database: &str) -> HttpClient<'_> {
 	let credentials = Credentials {
         username: "gobwas",
         password: "xxxx",
-        database: "test"
+        database
     };
 
     let client = Arc::new(create_client(credentials, vec!["http://localhost:8086"]));
@@ -22,8 +22,8 @@ fn before<'a>() -> HttpClient<'a> {
         let client = client.clone();
         let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
         rt.block_on(
-            client.query("drop database test".to_string(), None).then(move |_| {
-                client.query("create database test".to_string(), None)
+            client.query(format!("drop database {}", database), None).then(move |_| {
+                client.query(format!("create database {}", database), None)
             }).map(|_| ()).map_err(|_| ())
         ).unwrap();
     }
@@ -36,7 +36,7 @@ fn before<'a>() -> HttpClient<'a> {
 
 #[test]
 fn test_write_measurement() {
-    let client = before();
+    let client = before("test");
 
     let mut measurement = Measurement::new("sut");
 
@@ -60,3 +60,24 @@ fn test_write_measurement() {
         assert_eq!(fixture, res);
     }).map_err(|e| println!("{:?}", e))).unwrap();
 }
+
+#[test]
+fn test_write_utf8_measurement() {
+    let client = before("test_utf8");
+
+    let mut measurement = Measurement::new("sut");
+
+    measurement.add_field("string", Value::String("строка 👍"));
+    measurement.add_tag("tag", "東京, 🗼");
+
+    measurement.set_timestamp(1_434_055_562_000_000_000);
+
+    let mut rt = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+    rt.block_on(client.write_one(measurement, None).then(move |_| {
+        client.query("select * from \"sut\"".to_string(), None)
+    }).map(|res| {
+        let fixture = "{\"results\":[{\"series\":[{\"name\":\"sut\",\"columns\":[\"time\",\"string\",\"tag\"],\"values\":[[\"2015-06-11T20:46:02Z\",\"строка 👍\",\"東京, 🗼\"]]}]}]}";
+        assert_eq!(fixture, res);
+    }).map_err(|e| println!("{:?}", e))).unwrap();
+}